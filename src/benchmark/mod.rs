@@ -0,0 +1,249 @@
+use std::{
+    fmt::{Display, Write as _},
+    fs::{create_dir_all, File},
+    io::Write as _,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::SmallRng, SeedableRng};
+use serde::Serialize;
+
+use crate::{
+    model::{order::Order, vehicle_info::VehicleInfo, Map as _},
+    schedule::Scheduler,
+    simulation::{
+        config::SimulatorConfig,
+        result::SimulationResult,
+        simulator::{Simulator, VehicleInitialPosition},
+    },
+};
+
+/// Creates a fresh scheduler for the given benchmark instance. Called once
+/// per instance from the worker threads.
+pub type SchedulerFactory<'a> = dyn Fn(i32) -> anyhow::Result<Box<dyn Scheduler>> + Sync + 'a;
+
+#[derive(Debug, Clone)]
+pub struct BenchmarkOptions {
+    pub instances: Vec<i32>,
+    pub threads: usize,
+    /// Wall-clock budget per instance. Checked between simulation events, so
+    /// it does not interrupt a scheduler call: a `remote:` or `subprocess:`
+    /// scheduler that never answers blocks its thread. Remote requests time
+    /// out on their own after ten minutes.
+    pub timeout: Option<Duration>,
+    pub config: SimulatorConfig,
+    /// Seed for the random initial vehicle positions, combined with the
+    /// instance number.
+    pub seed: u64,
+}
+
+impl Default for BenchmarkOptions {
+    fn default() -> Self {
+        Self {
+            instances: crate::model::ALL_INSTANCES.clone().collect(),
+            threads: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            timeout: None,
+            config: SimulatorConfig::default(),
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BenchmarkStatus {
+    Completed,
+    Timeout,
+    Failed(String),
+}
+
+impl Display for BenchmarkStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Completed => write!(f, "completed"),
+            Self::Timeout => write!(f, "timeout"),
+            Self::Failed(err) => write!(f, "failed: {err}"),
+        }
+    }
+}
+
+impl Serialize for BenchmarkStatus {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkRow {
+    pub instance: i32,
+    pub orders: usize,
+    pub vehicles: usize,
+    pub status: BenchmarkStatus,
+    /// Total timeout in hours.
+    pub total_timeout: f64,
+    pub distance: f32,
+    pub objective: f64,
+    /// Wall time in seconds.
+    pub wall_time: f64,
+}
+
+impl BenchmarkRow {
+    fn new(instance: i32) -> Self {
+        Self {
+            instance,
+            orders: 0,
+            vehicles: 0,
+            status: BenchmarkStatus::Completed,
+            total_timeout: f64::NAN,
+            distance: f32::NAN,
+            objective: f64::NAN,
+            wall_time: 0.0,
+        }
+    }
+
+    fn set_result(&mut self, result: &SimulationResult) {
+        self.total_timeout = result.total_timeout_hours();
+        self.distance = result.total_distance;
+        self.objective = result.objective;
+    }
+}
+
+/// Runs the scheduler over all requested instances in parallel and returns
+/// one row per instance, sorted by instance number.
+pub fn run_benchmark(
+    options: &BenchmarkOptions,
+    make_scheduler: &SchedulerFactory<'_>,
+) -> Vec<BenchmarkRow> {
+    let next = AtomicUsize::new(0);
+    let rows = Mutex::new(Vec::with_capacity(options.instances.len()));
+    std::thread::scope(|scope| {
+        for _ in 0..options.threads.max(1) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&instance) = options.instances.get(index) else {
+                    break;
+                };
+                let row = run_instance(options, instance, make_scheduler);
+                rows.lock().unwrap().push(row);
+            });
+        }
+    });
+
+    let mut rows = rows.into_inner().unwrap();
+    rows.sort_by_key(|row| row.instance);
+    rows
+}
+
+fn run_instance(
+    options: &BenchmarkOptions,
+    instance: i32,
+    make_scheduler: &SchedulerFactory<'_>,
+) -> BenchmarkRow {
+    let start = Instant::now();
+    let mut row = BenchmarkRow::new(instance);
+    if let Err(err) = simulate_instance(options, instance, make_scheduler, start, &mut row) {
+        row.status = BenchmarkStatus::Failed(format!("{err:#}"));
+    }
+    row.wall_time = start.elapsed().as_secs_f64();
+    row
+}
+
+fn simulate_instance(
+    options: &BenchmarkOptions,
+    instance: i32,
+    make_scheduler: &SchedulerFactory<'_>,
+    start: Instant,
+    row: &mut BenchmarkRow,
+) -> anyhow::Result<()> {
+    row.orders = Order::load_instance(instance)?.keys().count();
+    row.vehicles = VehicleInfo::load_instance(instance)?.keys().count();
+
+    let mut rng = SmallRng::seed_from_u64(options.seed ^ instance as u64);
+    let mut sim = Simulator::builder(instance, VehicleInitialPosition::Random(&mut rng))
+        .config(options.config.clone())
+        .scheduler(make_scheduler(instance)?)
+        .build()?;
+    while !sim.is_finished() {
+        if options
+            .timeout
+            .is_some_and(|timeout| start.elapsed() > timeout)
+        {
+            row.status = BenchmarkStatus::Timeout;
+            break;
+        }
+        sim.simulate_step()?;
+    }
+
+    row.set_result(&sim.result());
+    Ok(())
+}
+
+pub fn write_csv(path: impl AsRef<Path>, rows: &[BenchmarkRow]) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    let mut writer = csv::Writer::from_path(path)?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn markdown_table(rows: &[BenchmarkRow]) -> String {
+    let mut table = String::from(
+        "| instance | orders | vehicles | status | total timeout (h) | distance | objective | wall time (s) |\n\
+         | ---: | ---: | ---: | --- | ---: | ---: | ---: | ---: |\n",
+    );
+    for row in rows {
+        let status = row.status.to_string().replace('|', "\\|");
+        writeln!(
+            table,
+            "| {} | {} | {} | {} | {:.3} | {:.1} | {:.3} | {:.2} |",
+            row.instance,
+            row.orders,
+            row.vehicles,
+            status,
+            row.total_timeout,
+            row.distance,
+            row.objective,
+            row.wall_time
+        )
+        .unwrap();
+    }
+    table
+}
+
+pub fn write_markdown(path: impl AsRef<Path>, rows: &[BenchmarkRow]) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    File::create(path)?.write_all(markdown_table(rows).as_bytes())?;
+    Ok(())
+}
+
+#[test]
+fn test_benchmark_small_instances() {
+    use crate::schedule::naive::NaiveScheduler;
+
+    let options = BenchmarkOptions {
+        instances: vec![1, 2],
+        threads: 2,
+        ..Default::default()
+    };
+    let rows = run_benchmark(&options, &|inst| Ok(Box::new(NaiveScheduler::new(inst)?)));
+    assert_eq!(rows.iter().map(|r| r.instance).collect::<Vec<_>>(), [1, 2]);
+    assert!(rows.iter().all(|r| r.status == BenchmarkStatus::Completed));
+    assert!(markdown_table(&rows).lines().count() == 4);
+}
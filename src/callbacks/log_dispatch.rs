@@ -1,5 +1,7 @@
 use std::{collections::BTreeMap, path::PathBuf};
 
+use anyhow::Context as _;
+
 use crate::{
     callbacks::dump_json,
     model::vehicle_info::VehicleId,
@@ -7,19 +9,28 @@ use crate::{
     simulation::{callback::SimulationCallback, simulator::VehicleRoute},
 };
 
+/// Default root directory of [`LogDispatchCallback`].
+pub const DEFAULT_LOG_DIR: &str = "logs";
+
+/// Writes `dispatch_input.json` and `dispatch_output.json` of every dispatch
+/// to `<root>/<name>/<iteration>/`.
 pub struct LogDispatchCallback {
+    root: PathBuf,
     name: String,
     iteration: usize,
 }
 
 impl LogDispatchCallback {
-    pub fn new(name: String) -> Self {
-        Self { name, iteration: 0 }
+    pub fn new(root: impl Into<PathBuf>, name: impl Into<String>) -> Self {
+        Self {
+            root: root.into(),
+            name: name.into(),
+            iteration: 0,
+        }
     }
 
     pub fn get_file(&self, filename: &str) -> PathBuf {
-        let mut dir = PathBuf::new();
-        dir.push("logs");
+        let mut dir = self.root.clone();
         dir.push(&self.name);
         dir.push(format!("{}", self.iteration));
         dir.push(filename);
@@ -30,6 +41,7 @@ impl LogDispatchCallback {
 impl Clone for LogDispatchCallback {
     fn clone(&self) -> Self {
         Self {
+            root: self.root.clone(),
             name: format!("{}_cloned", self.name),
             iteration: self.iteration,
         }
@@ -37,16 +49,39 @@ impl Clone for LogDispatchCallback {
 }
 
 impl SimulationCallback for LogDispatchCallback {
-    fn visit_dispatch_input(&mut self, input: &SchedulerArgs) {
-        if let Err(err) = dump_json(self.get_file("dispatch_input.json"), &input) {
-            eprintln!("Failed to write dispatch input JSON file: {}", err);
-        }
+    fn visit_dispatch_input(&mut self, input: &SchedulerArgs) -> anyhow::Result<()> {
+        let path = self.get_file("dispatch_input.json");
+        dump_json(&path, input).with_context(|| format!("unable to write {}", path.display()))
     }
 
-    fn visit_dispatch_output(&mut self, output: &BTreeMap<VehicleId, Vec<VehicleRoute>>) {
-        if let Err(err) = dump_json(self.get_file("dispatch_output.json"), output) {
-            eprintln!("Failed to write dispatch output JSON file: {}", err);
-        }
-        self.iteration += 1
+    fn visit_dispatch_output(
+        &mut self,
+        output: &BTreeMap<VehicleId, Vec<VehicleRoute>>,
+    ) -> anyhow::Result<()> {
+        let path = self.get_file("dispatch_output.json");
+        dump_json(&path, output).with_context(|| format!("unable to write {}", path.display()))?;
+        self.iteration += 1;
+        Ok(())
     }
 }
+
+#[test]
+fn test_log_dispatch_writes_files() {
+    use crate::testing;
+    use chrono::Duration;
+
+    let root = std::env::temp_dir().join(format!("dpdp_logs_{}", std::process::id()));
+    let mut sim = testing::simulator()
+        .callback(Box::new(LogDispatchCallback::new(&root, "test")))
+        .build()
+        .unwrap();
+    // dispatches at 00:00 and 01:40
+    sim.simulate_until(sim.start_time() + Duration::hours(2))
+        .unwrap();
+
+    let dir = root.join("test").join("1");
+    let args = SchedulerArgs::load(dir.join("dispatch_input.json")).unwrap();
+    assert_eq!(args.time, sim.start_time() + Duration::minutes(100));
+    assert!(dir.join("dispatch_output.json").exists());
+    std::fs::remove_dir_all(root).unwrap();
+}
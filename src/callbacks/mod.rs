@@ -1,16 +1,23 @@
 use std::{
     fs::{create_dir_all, File},
+    io::BufWriter,
     path::Path,
 };
 
 use serde::Serialize;
 
 use serde_json::ser::{Formatter, PrettyFormatter};
-use serde_json::{Serializer, Value};
+use serde_json::Value;
 use std::io::{Result as IoResult, Write};
 
 pub mod log_dispatch;
+pub mod trace;
 
+/// Arrays of at most this many scalars are written on a single line.
+const MAX_INLINE_ARRAY_LEN: usize = 16;
+
+/// Writes `value` as pretty-printed JSON, keeping short arrays of scalars
+/// (such as item stacks) on one line.
 pub fn dump_json<T>(path: impl AsRef<Path>, value: &T) -> anyhow::Result<()>
 where
     T: ?Sized + Serialize,
@@ -20,7 +27,10 @@ where
         create_dir_all(parent)?;
     }
 
-    serde_json::to_string_pretty(value);
+    let value = serde_json::to_value(value)?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    CompactArrayFormatter::new(MAX_INLINE_ARRAY_LEN).write_value(&mut writer, &value)?;
+    writer.flush()?;
     Ok(())
 }
 
@@ -37,15 +47,51 @@ impl CompactArrayFormatter {
         }
     }
 
-    fn is_small_array(&self, value: &Value) -> bool {
+    fn is_small_array(&self, value: &[Value]) -> bool {
+        value.len() <= self.max_inline_len
+            && value
+                .iter()
+                .all(|v| !matches!(v, Value::Array(_) | Value::Object(_)))
+    }
+
+    fn write_value<W: ?Sized + Write>(&mut self, writer: &mut W, value: &Value) -> IoResult<()> {
         match value {
-            Value::Array(arr) => {
-                arr.len() <= self.max_inline_len
-                    && arr
-                        .iter()
-                        .all(|v| !matches!(v, Value::Array(_) | Value::Object(_)))
+            Value::Array(arr) => self.write_array(writer, arr),
+            Value::Object(map) => {
+                self.begin_object(writer)?;
+                for (i, (key, v)) in map.iter().enumerate() {
+                    self.begin_object_key(writer, i == 0)?;
+                    serde_json::to_writer(&mut *writer, key)?;
+                    self.end_object_key(writer)?;
+                    self.begin_object_value(writer)?;
+                    self.write_value(writer, v)?;
+                    self.end_object_value(writer)?;
+                }
+                self.end_object(writer)
+            }
+            scalar => Ok(serde_json::to_writer(&mut *writer, scalar)?),
+        }
+    }
+
+    fn write_array<W: ?Sized + Write>(&mut self, writer: &mut W, value: &[Value]) -> IoResult<()> {
+        if self.is_small_array(value) {
+            write!(writer, "[")?;
+            for (i, v) in value.iter().enumerate() {
+                if i > 0 {
+                    write!(writer, ", ")?;
+                }
+                write!(writer, "{}", v)?;
+            }
+            write!(writer, "]")
+        } else {
+            // fallback to default pretty array
+            self.begin_array(writer)?;
+            for (i, v) in value.iter().enumerate() {
+                self.begin_array_value(writer, i == 0)?;
+                self.write_value(writer, v)?;
+                self.end_array_value(writer)?;
             }
-            _ => false,
+            self.end_array(writer)
         }
     }
 }
@@ -71,32 +117,6 @@ impl Formatter for CompactArrayFormatter {
         self.inner.end_array_value(writer)
     }
 
-    fn write_array<W: ?Sized + Write>(&mut self, writer: &mut W, value: &[Value]) -> IoResult<()> {
-        if value.len() <= self.max_inline_len
-            && value
-                .iter()
-                .all(|v| !matches!(v, Value::Array(_) | Value::Object(_)))
-        {
-            write!(writer, "[")?;
-            for (i, v) in value.iter().enumerate() {
-                if i > 0 {
-                    write!(writer, ", ")?;
-                }
-                write!(writer, "{}", v)?;
-            }
-            write!(writer, "]")
-        } else {
-            // fallback to default pretty array
-            self.begin_array(writer)?;
-            for (i, v) in value.iter().enumerate() {
-                self.begin_array_value(writer, i == 0)?;
-                v.serialize(&mut Serializer::with_formatter(writer, &mut self.inner))?;
-                self.end_array_value(writer)?;
-            }
-            self.end_array(writer)
-        }
-    }
-
     // Delegate the rest to PrettyFormatter
     fn begin_object<W: ?Sized + Write>(&mut self, writer: &mut W) -> IoResult<()> {
         self.inner.begin_object(writer)
@@ -130,3 +150,19 @@ impl Formatter for CompactArrayFormatter {
         self.inner.write_raw_fragment(writer, fragment)
     }
 }
+
+#[test]
+fn test_dump_json_compact_arrays() {
+    let path = std::env::temp_dir().join(format!("dpdp_dump_{}.json", std::process::id()));
+    let value = serde_json::json!({
+        "stack": ["a", "b"],
+        "routes": [{ "items": [1, 2, 3] }],
+    });
+    dump_json(&path, &value).unwrap();
+    let written = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(written.contains(r#""stack": ["a", "b"]"#));
+    assert!(written.contains(r#""items": [1, 2, 3]"#));
+    assert_eq!(serde_json::from_str::<Value>(&written).unwrap(), value);
+}
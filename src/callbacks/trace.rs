@@ -0,0 +1,109 @@
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    model::vehicle_info::VehicleId,
+    simulation::{
+        callback::SimulationCallback,
+        simulator::{Checkpoint, SimEvent, Simulator, VehicleRoute},
+    },
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TraceEntry {
+    Event(SimEvent),
+    Dispatch(BTreeMap<VehicleId, Vec<VehicleRoute>>),
+}
+
+/// Records every event and dispatch output of a simulation.
+///
+/// The trace is a JSON lines file: a [`Checkpoint`] of the simulator when
+/// recording started, followed by one [`TraceEntry`] per line. Clones, e.g.
+/// in forked simulators, do not record anything.
+pub struct TraceRecorder {
+    path: PathBuf,
+    writer: Option<BufWriter<File>>,
+}
+
+impl TraceRecorder {
+    /// Starts a trace of `sim` from its current state. Add the recorder with
+    /// [`Simulator::add_callback`] before simulating any further.
+    pub fn new(path: impl Into<PathBuf>, sim: &Simulator) -> anyhow::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        let file =
+            File::create(&path).with_context(|| format!("unable to create {}", path.display()))?;
+        let mut recorder = Self {
+            path,
+            writer: Some(BufWriter::new(file)),
+        };
+        recorder.write_line(&sim.checkpoint())?;
+        Ok(recorder)
+    }
+
+    fn write_line(&mut self, value: &impl Serialize) -> anyhow::Result<()> {
+        let Some(writer) = self.writer.as_mut() else {
+            return Ok(());
+        };
+        let context = || format!("unable to write {}", self.path.display());
+        serde_json::to_writer(&mut *writer, value).with_context(context)?;
+        writeln!(writer).with_context(context)
+    }
+}
+
+impl Clone for TraceRecorder {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            writer: None,
+        }
+    }
+}
+
+impl SimulationCallback for TraceRecorder {
+    fn visit_event(&mut self, event: &SimEvent) -> anyhow::Result<()> {
+        self.write_line(&TraceEntry::Event(event.clone()))
+    }
+
+    fn visit_dispatch_output(
+        &mut self,
+        output: &BTreeMap<VehicleId, Vec<VehicleRoute>>,
+    ) -> anyhow::Result<()> {
+        self.write_line(&TraceEntry::Dispatch(output.clone()))?;
+        // keep the trace usable if the run is aborted later
+        if let Some(writer) = self.writer.as_mut() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a trace written by [`TraceRecorder`].
+pub fn read_trace(path: impl AsRef<Path>) -> anyhow::Result<(Checkpoint, Vec<TraceEntry>)> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("unable to open {}", path.display()))?;
+    let mut lines = BufReader::new(file).lines();
+    let checkpoint = serde_json::from_str(
+        &lines
+            .next()
+            .with_context(|| format!("empty trace {}", path.display()))??,
+    )
+    .with_context(|| format!("invalid checkpoint in {}", path.display()))?;
+    let entries = lines
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(&line?)
+                .with_context(|| format!("invalid entry on line {} of {}", i + 2, path.display()))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok((checkpoint, entries))
+}
@@ -1,6 +1,10 @@
 #![allow(dead_code)]
 
+pub mod benchmark;
 pub mod callbacks;
 pub mod model;
 pub mod schedule;
 pub mod simulation;
+
+#[cfg(test)]
+mod testing;
@@ -1,37 +1,345 @@
-use chrono::{Duration, Local, NaiveTime};
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
 use dpdp_rust::{
-    callbacks::log_dispatch::LogDispatchCallback,
-    model::{
-        factory_info::FactoryId,
-        route_info::{RouteInfo, RouteMap},
-        vehicle_info::VehicleId,
+    benchmark::{self, BenchmarkOptions},
+    callbacks::{
+        log_dispatch::{LogDispatchCallback, DEFAULT_LOG_DIR},
+        trace::TraceRecorder,
+    },
+    model::validate::{load_std_map, validate_instance, validate_routes},
+    schedule::{
+        create_scheduler,
+        offline::{OfflineConfig, OfflineScheduler, OfflineSolver},
+        SCHEDULER_NAMES,
+    },
+    simulation::{
+        config::SimulatorConfig,
+        objective::Objective,
+        replay::Replayer,
+        simulator::{Simulator, VehicleInitialPosition},
     },
-    simulation::simulator::{Simulator, VehicleInitialPosition},
 };
-use rand::rngs::SmallRng;
+use rand::{rngs::SmallRng, SeedableRng};
 
-fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt::init();
-    // let mut rng = SmallRng::seed_from_u64(727);
-    // let mut sim = Simulator::new(VehicleInitialPosition::Random(&mut rng), 2)?;
-    let mut sim = Simulator::new(
-        VehicleInitialPosition::<SmallRng>::Deterministic(
-            [
-                ("V_1", "e2d5093fbe36431f8986ddb0e1c586be"),
-                ("V_2", "7fe14b93f0f04ee7a994ef5b2c1fdb72"),
-                ("V_3", "fa366fc87a124d32926daa5bb093129f"),
-                ("V_4", "e47399648fa842b2b8f80094343d8091"),
-                ("V_5", "becb4f85393540b287e7329758b8d832"),
-            ]
-            .map(|(vid, fid)| (VehicleId(vid.to_string()), FactoryId(fid.to_string())))
-            .into(),
-        ),
-        1,
-        vec![Box::new(LogDispatchCallback::new("test".into()))],
-    )?;
-    sim.simulate_until(
-        Local::now().date_naive().and_time(NaiveTime::MIN) + Duration::minutes(200000),
+#[derive(Parser)]
+#[command(about = "Dynamic pickup and delivery problem simulator")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct CommonArgs {
+    /// Scheduler used for dispatching: alns, alns:<budget> (e.g. alns:30s),
+    /// anticipatory, insertion, naive, noop, offline, offline:<time limit>,
+    /// remote:<url>, rollout or subprocess:<command> (split like a shell
+    /// command line and run in `subprocess/instance_<n>`, so relative paths
+    /// must be given from there)
+    #[arg(long, default_value = "naive", value_parser = parse_scheduler)]
+    scheduler: String,
+    /// Simulator config file (TOML or JSON)
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Seed for the random initial vehicle positions
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Show all orders to the scheduler from the start
+    #[arg(long)]
+    clairvoyant: bool,
+}
+
+impl CommonArgs {
+    fn config(&self) -> anyhow::Result<SimulatorConfig> {
+        let mut config: SimulatorConfig = self
+            .config
+            .as_ref()
+            .map(SimulatorConfig::load)
+            .transpose()?
+            .unwrap_or_default();
+        config.clairvoyant |= self.clairvoyant;
+        Ok(config)
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Simulate a single instance
+    Run {
+        #[arg(long, default_value_t = 1)]
+        instance: i32,
+        #[command(flatten)]
+        common: CommonArgs,
+        /// CSV file with `vehicle_id,factory_id` initial positions, random
+        /// positions are used if omitted
+        #[arg(long)]
+        positions: Option<PathBuf>,
+        /// Log every dispatch input and output under the given directory
+        #[arg(long, value_name = "DIR", num_args = 0..=1, default_missing_value = DEFAULT_LOG_DIR)]
+        log_dispatch: Option<PathBuf>,
+        /// Record all events and dispatches to a trace file for `replay`
+        #[arg(long)]
+        trace: Option<PathBuf>,
+        /// Directory for `result.json`, `orders.csv` and `vehicles.csv`
+        #[arg(long, default_value = "output")]
+        output: PathBuf,
+    },
+    /// Re-execute a trace recorded by `run --trace` and check that the
+    /// simulator still produces the same events
+    Replay { trace: PathBuf },
+    /// Run a scheduler over many instances in parallel
+    Batch {
+        /// Instances to run, e.g. `all` or `1,2,10-20`
+        #[arg(long, default_value = "all")]
+        instances: String,
+        #[command(flatten)]
+        common: CommonArgs,
+        /// Number of worker threads, defaults to the number of CPUs
+        #[arg(long)]
+        threads: Option<usize>,
+        /// Wall-clock budget per instance, e.g. `10m`
+        #[arg(long, value_parser = humantime::parse_duration)]
+        timeout: Option<std::time::Duration>,
+        /// Directory for `summary.csv` and `summary.md`
+        #[arg(long, default_value = "benchmark")]
+        output: PathBuf,
+    },
+    /// Solve an instance with all orders known in advance, giving a bound
+    /// under fixed order splitting on what any scheduler can achieve
+    Offline {
+        #[arg(long, default_value_t = 1)]
+        instance: i32,
+        /// Simulator config file (TOML or JSON)
+        #[arg(long)]
+        config: Option<PathBuf>,
+        /// Seed for the random initial vehicle positions
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// CSV file with `vehicle_id,factory_id` initial positions, random
+        /// positions are used if omitted
+        #[arg(long)]
+        positions: Option<PathBuf>,
+        /// Search budget, the best schedule found and a lower bound under
+        /// fixed order splitting are reported when it runs out
+        #[arg(long, default_value = "60s", value_parser = humantime::parse_duration)]
+        time_limit: std::time::Duration,
+    },
+    /// Check the consistency of the benchmark data
+    ValidateData {
+        /// Instances to check, e.g. `all` or `1,2,10-20`
+        #[arg(long, default_value = "all")]
+        instances: String,
+    },
+}
+
+/// Checks the name against [`SCHEDULER_NAMES`] without creating the
+/// scheduler, which may load instance data or connect to a service.
+fn parse_scheduler(name: &str) -> anyhow::Result<String> {
+    for pattern in SCHEDULER_NAMES {
+        let Some((prefix, placeholder)) = pattern.split_once(':') else {
+            if name == *pattern {
+                return Ok(name.to_string());
+            }
+            continue;
+        };
+        let Some(arg) = name.strip_prefix(prefix).and_then(|s| s.strip_prefix(':')) else {
+            continue;
+        };
+        if matches!(placeholder, "<budget>" | "<time limit>") {
+            humantime::parse_duration(arg)?;
+        }
+        let missing = if placeholder == "<command>" {
+            shell_words::split(arg)?.is_empty()
+        } else {
+            arg.trim().is_empty()
+        };
+        if missing {
+            return Err(anyhow::anyhow!("missing {placeholder} in {name}"));
+        }
+        return Ok(name.to_string());
+    }
+    Err(anyhow::anyhow!(
+        "unknown scheduler {name}, expected one of {SCHEDULER_NAMES:?}"
+    ))
+}
+
+fn parse_instances(spec: &str) -> anyhow::Result<Vec<i32>> {
+    if spec == "all" {
+        return Ok(BenchmarkOptions::default().instances);
+    }
+    let mut instances = Vec::new();
+    for part in spec.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => instances.extend(from.trim().parse::<i32>()?..=to.trim().parse()?),
+            None => instances.push(part.trim().parse()?),
+        }
+    }
+    Ok(instances)
+}
+
+fn run(
+    instance: i32,
+    common: CommonArgs,
+    positions: Option<PathBuf>,
+    log_dispatch: Option<PathBuf>,
+    trace: Option<PathBuf>,
+    output: PathBuf,
+) -> anyhow::Result<()> {
+    let mut rng = SmallRng::seed_from_u64(common.seed);
+    let initial_position = match positions {
+        Some(path) => VehicleInitialPosition::load(path)?,
+        None => VehicleInitialPosition::Random(&mut rng),
+    };
+    let mut builder = Simulator::builder(instance, initial_position)
+        .config(common.config()?)
+        .scheduler(create_scheduler(&common.scheduler, instance)?);
+    if let Some(root) = log_dispatch {
+        builder = builder.callback(Box::new(LogDispatchCallback::new(
+            root,
+            format!("instance_{instance}"),
+        )));
+    }
+    let mut sim = builder.build()?;
+    if let Some(trace) = trace {
+        let recorder = TraceRecorder::new(trace, &sim)?;
+        sim.add_callback(Box::new(recorder));
+    }
+
+    let result = sim.run_to_completion()?;
+    result.write_json(output.join("result.json"))?;
+    result.write_orders_csv(output.join("orders.csv"))?;
+    result.write_vehicles_csv(output.join("vehicles.csv"))?;
+    println!("{}", result.summary());
+    Ok(())
+}
+
+fn batch(
+    instances: String,
+    common: CommonArgs,
+    threads: Option<usize>,
+    timeout: Option<std::time::Duration>,
+    output: PathBuf,
+) -> anyhow::Result<()> {
+    let defaults = BenchmarkOptions::default();
+    let options = BenchmarkOptions {
+        instances: parse_instances(&instances)?,
+        threads: threads.unwrap_or(defaults.threads),
+        timeout,
+        config: common.config()?,
+        seed: common.seed,
+    };
+    let rows =
+        benchmark::run_benchmark(&options, &|inst| create_scheduler(&common.scheduler, inst));
+    benchmark::write_csv(output.join("summary.csv"), &rows)?;
+    benchmark::write_markdown(output.join("summary.md"), &rows)?;
+    print!("{}", benchmark::markdown_table(&rows));
+    Ok(())
+}
+
+fn replay(trace: PathBuf) -> anyhow::Result<()> {
+    let sim = Replayer::load(&trace)?.replay()?;
+    println!("{}", sim.result().summary());
+    println!("replayed {} without differences", trace.display());
+    Ok(())
+}
+
+fn offline(
+    instance: i32,
+    config: Option<PathBuf>,
+    seed: u64,
+    positions: Option<PathBuf>,
+    time_limit: std::time::Duration,
+) -> anyhow::Result<()> {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let initial_position = match positions {
+        Some(path) => VehicleInitialPosition::load(path)?,
+        None => VehicleInitialPosition::Random(&mut rng),
+    };
+    let config = config
+        .map(SimulatorConfig::load)
+        .transpose()?
+        .unwrap_or_default();
+    let sim = Simulator::builder(instance, initial_position)
+        .config(SimulatorConfig {
+            clairvoyant: true,
+            ..config
+        })
+        .build()?;
+    let solution = OfflineSolver::new(&sim)?.solve(&OfflineConfig {
+        time_limit,
+        ..Default::default()
+    });
+
+    // the solver assumes free docks, the simulated schedule is what is achieved
+    let competition = match sim.config().objective {
+        objective @ Objective::Competition { .. } => objective,
+        _ => Objective::default(),
+    };
+    let scheduler = OfflineScheduler::with_plan(solution.plan.clone());
+    let result = sim.fork(Box::new(scheduler), None).run_to_completion()?;
+    println!("{}", result.summary());
+    println!(
+        "achieved objective {:.3} (solver objective {:.3}, differs when vehicles wait for docks)",
+        competition.evaluate(&result),
+        solution.objective
+    );
+    println!(
+        "lower bound under fixed order splitting {:.3} ({}), {} nodes",
+        solution.lower_bound,
+        if solution.is_optimal() {
+            "optimal".to_string()
+        } else {
+            format!("gap {:.1}%", solution.gap() * 100.0)
+        },
+        solution.nodes
     );
-    // sim.simulate_until(Utc::now().naive_utc() + Duration::hours(2));
     Ok(())
 }
+
+fn validate_data(instances: String) -> anyhow::Result<()> {
+    let (factories, routes) = load_std_map()?;
+    let mut num_problems = 0;
+    for problem in validate_routes(&factories, &routes) {
+        println!("routes: {problem}");
+        num_problems += 1;
+    }
+    for inst in parse_instances(&instances)? {
+        for problem in validate_instance(inst, &factories, &routes)? {
+            println!("instance {inst}: {problem}");
+            num_problems += 1;
+        }
+    }
+    anyhow::ensure!(num_problems == 0, "found {num_problems} problems");
+    println!("all data is valid");
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+    match Cli::parse().command {
+        Command::Run {
+            instance,
+            common,
+            positions,
+            log_dispatch,
+            trace,
+            output,
+        } => run(instance, common, positions, log_dispatch, trace, output),
+        Command::Replay { trace } => replay(trace),
+        Command::Batch {
+            instances,
+            common,
+            threads,
+            timeout,
+            output,
+        } => batch(instances, common, threads, timeout, output),
+        Command::Offline {
+            instance,
+            config,
+            seed,
+            positions,
+            time_limit,
+        } => offline(instance, config, seed, positions, time_limit),
+        Command::ValidateData { instances } => validate_data(instances),
+    }
+}
@@ -24,7 +24,7 @@ impl Display for FactoryId {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FactoryInfo {
     pub factory_id: FactoryId,
     pub longitude: f64,
@@ -12,11 +12,12 @@ pub mod factory_info;
 pub mod order;
 pub mod order_item;
 pub mod route_info;
+pub mod validate;
 pub mod vehicle_info;
 
-static ALL_INSTANCES: RangeInclusive<i32> = 1..=64;
+pub static ALL_INSTANCES: RangeInclusive<i32> = 1..=64;
 
-fn read_csv<T>(path: impl AsRef<Path>) -> anyhow::Result<Vec<T>>
+pub(crate) fn read_csv<T>(path: impl AsRef<Path>) -> anyhow::Result<Vec<T>>
 where
     T: DeserializeOwned,
 {
@@ -41,6 +42,38 @@ where
     Ok(chrono::Duration::seconds(s))
 }
 
+pub(crate) fn serialize_duration<S>(
+    duration: &chrono::Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_i64(duration.num_seconds())
+}
+
+/// Serializes any map defined by [`define_map!`] as a plain map.
+pub(crate) fn serialize_map<M, K, V, S>(map: &M, serializer: S) -> Result<S::Ok, S::Error>
+where
+    M: Borrow<MapType<K, V>>,
+    K: serde::Serialize,
+    V: serde::Serialize,
+    S: serde::Serializer,
+{
+    serde::Serialize::serialize(map.borrow(), serializer)
+}
+
+/// Counterpart of [`serialize_map`].
+pub(crate) fn deserialize_map<'de, M, K, V, D>(deserializer: D) -> Result<M, D::Error>
+where
+    M: From<MapType<K, V>>,
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    MapType::<K, V>::deserialize(deserializer).map(M::from)
+}
+
 pub trait Map<K, V>: BorrowMut<MapType<K, V>> + Into<MapType<K, V>>
 where
     K: Eq + Ord + 'static,
@@ -5,18 +5,17 @@ use std::{
 };
 
 use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
-use serde::Deserialize;
-use serde_json::map::VacantEntry;
+use serde::{Deserialize, Serialize};
 
 use crate::define_map;
 
 use super::{
     factory_info::FactoryId,
     order_item::{OrderItem, OrderItemId, OrderItemType},
-    read_csv, Map, MapType,
+    read_csv, MapType,
 };
 
-#[derive(Clone, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct OrderId(pub(super) String);
 
 impl Debug for OrderId {
@@ -31,7 +30,7 @@ impl Display for OrderId {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Order {
     pub order_id: OrderId,
     pub q_standard: i32,
@@ -42,9 +41,15 @@ pub struct Order {
     pub creation_time: NaiveTime,
     #[serde(deserialize_with = "super::parse_naive_time")]
     committed_completion_time: NaiveTime,
-    #[serde(deserialize_with = "super::parse_duration")]
+    #[serde(
+        serialize_with = "super::serialize_duration",
+        deserialize_with = "super::parse_duration"
+    )]
     pub load_time: Duration,
-    #[serde(deserialize_with = "super::parse_duration")]
+    #[serde(
+        serialize_with = "super::serialize_duration",
+        deserialize_with = "super::parse_duration"
+    )]
     pub unload_time: Duration,
     pub pickup_id: FactoryId,
     pub delivery_id: FactoryId,
@@ -59,6 +64,18 @@ impl Order {
         date_time
     }
 
+    /// Copy of the order under another id, created at `creation_time` and
+    /// committed to be completed as long after creation as the original.
+    pub fn resampled(&self, order_id: String, creation_time: NaiveTime) -> Order {
+        let lead_time = self.committed_completion_time - self.creation_time;
+        Order {
+            order_id: OrderId(order_id),
+            creation_time,
+            committed_completion_time: creation_time + lead_time,
+            ..self.clone()
+        }
+    }
+
     pub fn load(path: impl AsRef<Path>) -> anyhow::Result<OrderMap> {
         Ok(read_csv::<Order>(path)?
             .into_iter()
@@ -7,13 +7,21 @@ use crate::define_map;
 
 use super::{factory_info::FactoryId, order::OrderId};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderItem {
     pub id: OrderItemId,
     pub demand: i32,
     pub creation_time: NaiveTime,
     pub(super) committed_completion_time: NaiveTime,
+    #[serde(
+        serialize_with = "super::serialize_duration",
+        deserialize_with = "super::parse_duration"
+    )]
     pub load_time: Duration,
+    #[serde(
+        serialize_with = "super::serialize_duration",
+        deserialize_with = "super::parse_duration"
+    )]
     pub unload_time: Duration,
     pub pickup_id: FactoryId,
     pub delivery_id: FactoryId,
@@ -56,15 +64,20 @@ impl<'de> Deserialize<'de> for OrderItemId {
         D: serde::Deserializer<'de>,
     {
         let str = String::deserialize(deserializer)?;
-        let mut parts = str.split('_');
-        let order_id = OrderId(parts.next().unwrap().to_string());
-        let item_type = match parts.next().unwrap() {
-            "standard" => OrderItemType::Standard,
-            "small" => OrderItemType::Small,
-            "box" => OrderItemType::Box,
-            _ => unreachable!(),
+        let invalid = || serde::de::Error::custom(format!("invalid order item id {str:?}"));
+        // order IDs may contain underscores, the type and index never do
+        let mut parts = str.rsplitn(3, '_');
+        let index = parts
+            .next()
+            .and_then(|index| index.parse().ok())
+            .ok_or_else(invalid)?;
+        let item_type = match parts.next() {
+            Some("standard") => OrderItemType::Standard,
+            Some("small") => OrderItemType::Small,
+            Some("box") => OrderItemType::Box,
+            _ => return Err(invalid()),
         };
-        let index = parts.next().unwrap().parse().unwrap();
+        let order_id = OrderId(parts.next().ok_or_else(invalid)?.to_string());
         Ok(OrderItemId {
             order_id,
             item_type,
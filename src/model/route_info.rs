@@ -1,12 +1,12 @@
 use core::f32;
-use std::path::Path;
+use std::{collections::HashSet, path::Path};
 
 use chrono::Duration;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::{factory_info::FactoryId, read_csv, MapType};
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RouteInfo {
     route_code: String,
     start_factory_id: FactoryId,
@@ -32,7 +32,9 @@ pub struct SingleRoute {
     time: i64,
 }
 
-#[derive(Debug, Clone)]
+/// Serialized as the list of its routes, like `route_info.csv`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "Vec<RouteInfo>", into = "Vec<RouteInfo>")]
 pub struct RouteMap {
     map: MapType<(FactoryId, FactoryId), SingleRoute>,
 }
@@ -54,7 +56,38 @@ impl From<Vec<RouteInfo>> for RouteMap {
     }
 }
 
+impl From<RouteMap> for Vec<RouteInfo> {
+    fn from(value: RouteMap) -> Self {
+        value
+            .map
+            .into_iter()
+            .map(|((start_factory_id, end_factory_id), r)| RouteInfo {
+                route_code: r.route_code,
+                start_factory_id,
+                end_factory_id,
+                distance: r.distance,
+                time: r.time,
+            })
+            .collect()
+    }
+}
+
 impl RouteMap {
+    /// Keeps only the routes between the given factories.
+    pub fn subset(&self, factories: &HashSet<&FactoryId>) -> RouteMap {
+        let map = self
+            .map
+            .iter()
+            .filter(|((from, to), _)| factories.contains(from) && factories.contains(to))
+            .map(|(key, route)| (key.clone(), route.clone()))
+            .collect();
+        RouteMap { map }
+    }
+
+    pub fn contains(&self, from: &FactoryId, to: &FactoryId) -> bool {
+        from == to || self.map.contains_key(&(from.clone(), to.clone()))
+    }
+
     pub fn query_time(&self, from: FactoryId, to: FactoryId) -> Duration {
         if from == to {
             return Duration::zero();
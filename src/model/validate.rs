@@ -0,0 +1,87 @@
+use super::{
+    factory_info::{FactoryInfo, FactoryInfoMap},
+    order::Order,
+    route_info::{RouteInfo, RouteMap},
+    vehicle_info::VehicleInfo,
+    Map,
+};
+
+/// Checks the consistency of a benchmark instance against the shared factory
+/// and route data. Returns one message per problem found.
+pub fn validate_instance(
+    inst_num: i32,
+    factories: &FactoryInfoMap,
+    routes: &RouteMap,
+) -> anyhow::Result<Vec<String>> {
+    let orders = Order::load_instance(inst_num)?;
+    let vehicles = VehicleInfo::load_instance(inst_num)?;
+    let mut problems = Vec::new();
+
+    if vehicles.keys().next().is_none() {
+        problems.push("no vehicles".to_string());
+    }
+    for vehicle in vehicles.values() {
+        if vehicle.capacity() <= 0 {
+            problems.push(format!(
+                "vehicle {} has capacity {}",
+                vehicle.car_num,
+                vehicle.capacity()
+            ));
+        }
+    }
+
+    for order in orders.values() {
+        for factory_id in [&order.pickup_id, &order.delivery_id] {
+            if !factories.contains_key(factory_id) {
+                problems.push(format!(
+                    "order {} references unknown factory {factory_id}",
+                    order.order_id
+                ));
+            }
+        }
+        if !routes.contains(&order.pickup_id, &order.delivery_id) {
+            problems.push(format!(
+                "order {} has no route from {} to {}",
+                order.order_id, order.pickup_id, order.delivery_id
+            ));
+        }
+        if order.calc_demand() <= 0 {
+            problems.push(format!("order {} has no items", order.order_id));
+        }
+        let demand = order.calc_demand() as f32 / 4.0;
+        if (demand - order.demand).abs() > 1e-3 {
+            problems.push(format!(
+                "order {} has demand {} but its items add up to {demand}",
+                order.order_id, order.demand
+            ));
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Checks that every pair of distinct factories is connected by a route.
+pub fn validate_routes(factories: &FactoryInfoMap, routes: &RouteMap) -> Vec<String> {
+    let mut problems = Vec::new();
+    for from in factories.keys() {
+        for to in factories.keys() {
+            if !routes.contains(from, to) {
+                problems.push(format!("no route from {from} to {to}"));
+            }
+        }
+    }
+    problems
+}
+
+pub fn load_std_map() -> anyhow::Result<(FactoryInfoMap, RouteMap)> {
+    Ok((FactoryInfo::load_std()?, RouteInfo::load_std()?.into()))
+}
+
+#[test]
+fn test_validate_std_data() {
+    let (factories, routes) = load_std_map().unwrap();
+    assert!(validate_routes(&factories, &routes).is_empty());
+    assert!(validate_instance(1, &factories, &routes)
+        .unwrap()
+        .is_empty());
+}
@@ -24,7 +24,7 @@ impl Display for VehicleId {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VehicleInfo {
     pub car_num: VehicleId,
     capacity: i32,
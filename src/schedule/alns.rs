@@ -0,0 +1,529 @@
+use std::time::{Duration, Instant};
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::{
+    model::{vehicle_info::VehicleId, MapType},
+    simulation::simulator::VehicleRoute,
+};
+
+use super::{
+    insertion::InsertionScheduler,
+    planning::{PlanningContext, Stop, Unit},
+    Scheduler, SchedulerArgs,
+};
+
+/// Parameters of [`AlnsScheduler`].
+#[derive(Debug, Clone)]
+pub struct AlnsConfig {
+    /// Wall-clock time per dispatch, including the construction of the
+    /// initial solution. Capped at 90% of the simulator's `time_interval`,
+    /// so a dispatch is never charged more than one interval for searching.
+    pub budget: Duration,
+    /// Stops the search after this many iterations, e.g. for reproducible
+    /// runs.
+    pub max_iterations: Option<usize>,
+    /// Largest fraction of the assigned orders removed by one destroy.
+    pub max_removal_fraction: f64,
+    pub max_removal: usize,
+    /// Regret insertion is tried with every `k` in `2..=regret_k`.
+    pub regret_k: usize,
+    /// Iterations between two weight updates.
+    pub segment_length: usize,
+    /// How fast the operator weights follow their recent scores.
+    pub reaction_factor: f64,
+    /// Probability of accepting a solution 5% worse than the current one at
+    /// the start of the search, strictly between 0 and 1.
+    pub initial_acceptance: f64,
+    pub cooling_rate: f64,
+    pub seed: u64,
+}
+
+impl Default for AlnsConfig {
+    fn default() -> Self {
+        Self {
+            budget: Duration::from_secs(10),
+            max_iterations: None,
+            max_removal_fraction: 0.3,
+            max_removal: 30,
+            regret_k: 3,
+            segment_length: 50,
+            reaction_factor: 0.2,
+            initial_acceptance: 0.5,
+            cooling_rate: 0.999,
+            seed: 0,
+        }
+    }
+}
+
+/// Adaptive large neighborhood search over the routes of all vehicles.
+///
+/// Starts from the [`InsertionScheduler`] solution and repeatedly removes
+/// orders with a destroy operator and reinserts them with a repair operator,
+/// picking both by roulette wheel with weights adapted to how often they
+/// improved the solution. Worse solutions are accepted with a simulated
+/// annealing criterion.
+pub struct AlnsScheduler {
+    config: AlnsConfig,
+    rng: SmallRng,
+}
+
+impl AlnsScheduler {
+    pub fn new(config: AlnsConfig) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            config.initial_acceptance > 0.0 && config.initial_acceptance < 1.0,
+            "initial acceptance {} is not between 0 and 1",
+            config.initial_acceptance
+        );
+        let rng = SmallRng::seed_from_u64(config.seed);
+        Ok(Self { config, rng })
+    }
+}
+
+impl Default for AlnsScheduler {
+    fn default() -> Self {
+        Self::new(AlnsConfig::default()).expect("the default config is valid")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Destroy {
+    Random,
+    WorstLateness,
+    Shaw,
+}
+
+const DESTROY_OPERATORS: [Destroy; 3] = [Destroy::Random, Destroy::WorstLateness, Destroy::Shaw];
+
+/// Scores of an operator pair whose result was a new best, an improvement
+/// or an accepted worse solution.
+const SCORE_BEST: f64 = 33.0;
+const SCORE_BETTER: f64 = 9.0;
+const SCORE_ACCEPTED: f64 = 13.0;
+
+/// Randomization of the ranked removals, higher removes the top ranked
+/// orders more deterministically.
+const WORST_DETERMINISM: i32 = 3;
+const SHAW_DETERMINISM: i32 = 6;
+
+/// Cost of an order left for a later dispatch, as if it was a day late.
+const UNASSIGNED_PENALTY_HOURS: f64 = 24.0;
+
+#[derive(Clone)]
+struct Solution {
+    routes: Vec<Vec<Stop>>,
+    costs: Vec<f64>,
+    /// Vehicle index of every unit.
+    assignment: Vec<Option<usize>>,
+}
+
+impl Solution {
+    fn cost(&self, ctx: &PlanningContext) -> f64 {
+        let unassigned = self.assignment.iter().filter(|v| v.is_none()).count();
+        self.costs.iter().sum::<f64>()
+            + unassigned as f64 * ctx.timeout_weight * UNASSIGNED_PENALTY_HOURS
+    }
+
+    fn remove(&mut self, ctx: &PlanningContext, units: &[Unit], unit: usize) {
+        let Some(v) = self.assignment[unit].take() else {
+            return;
+        };
+        let key = &units[unit].items[0];
+        self.routes[v]
+            .retain(|stop| stop.load.first() != Some(key) && stop.unload.first() != Some(key));
+        self.costs[v] = ctx
+            .route_cost(&ctx.vehicles[v], &self.routes[v])
+            .unwrap_or(f64::INFINITY);
+    }
+}
+
+struct Search<'a, 'b> {
+    ctx: &'a PlanningContext<'b>,
+    units: Vec<Unit>,
+    rng: &'a mut SmallRng,
+}
+
+impl Search<'_, '_> {
+    fn initial_solution(&self) -> Solution {
+        let routes = InsertionScheduler::construct(self.ctx);
+        let costs = self
+            .ctx
+            .vehicles
+            .iter()
+            .zip(routes.iter())
+            .map(|(v, stops)| self.ctx.route_cost(v, stops).unwrap_or(f64::INFINITY))
+            .collect();
+        let assignment = self
+            .units
+            .iter()
+            .map(|unit| {
+                let key = &unit.items[0];
+                routes
+                    .iter()
+                    .position(|stops| stops.iter().any(|s| s.load.first() == Some(key)))
+            })
+            .collect();
+        Solution {
+            routes,
+            costs,
+            assignment,
+        }
+    }
+
+    fn removal_count(&mut self, config: &AlnsConfig, solution: &Solution) -> usize {
+        let assigned = solution.assignment.iter().flatten().count();
+        let max = ((assigned as f64 * config.max_removal_fraction) as usize)
+            .min(config.max_removal)
+            .max(1)
+            .min(assigned);
+        if max == 0 {
+            0
+        } else {
+            self.rng.random_range(1..=max)
+        }
+    }
+
+    /// Picks from a list ranked best first, preferring the top.
+    fn pick_ranked(&mut self, len: usize, determinism: i32) -> usize {
+        let y: f64 = self.rng.random();
+        ((y.powi(determinism) * len as f64) as usize).min(len - 1)
+    }
+
+    fn destroy(&mut self, op: Destroy, solution: &mut Solution, count: usize) -> Vec<usize> {
+        let mut assigned: Vec<usize> = (0..self.units.len())
+            .filter(|&u| solution.assignment[u].is_some())
+            .collect();
+        if count == 0 || assigned.is_empty() {
+            return Vec::new();
+        }
+        let mut removed = Vec::with_capacity(count);
+        match op {
+            Destroy::Random => {
+                for _ in 0..count {
+                    let i = self.rng.random_range(0..assigned.len());
+                    removed.push(assigned.swap_remove(i));
+                }
+            }
+            Destroy::WorstLateness => {
+                let mut lateness = MapType::new();
+                for (vehicle, stops) in self.ctx.vehicles.iter().zip(solution.routes.iter()) {
+                    if let Some(cost) = self.ctx.evaluate(vehicle, stops) {
+                        lateness.extend(cost.late_orders);
+                    }
+                }
+                let lateness_of = |u: &usize| {
+                    lateness
+                        .get(&self.units[*u].order_id)
+                        .copied()
+                        .unwrap_or_default()
+                };
+                assigned.sort_by_key(|u| std::cmp::Reverse(lateness_of(u)));
+                for _ in 0..count {
+                    let i = self.pick_ranked(assigned.len(), WORST_DETERMINISM);
+                    removed.push(assigned.remove(i));
+                }
+            }
+            Destroy::Shaw => {
+                let seed = assigned.swap_remove(self.rng.random_range(0..assigned.len()));
+                removed.push(seed);
+                let relatedness = self.shaw_relatedness(seed, &assigned);
+                let mut ranked: Vec<_> = assigned.into_iter().zip(relatedness).collect();
+                ranked.sort_by(|a, b| a.1.total_cmp(&b.1));
+                for _ in 1..count {
+                    let i = self.pick_ranked(ranked.len(), SHAW_DETERMINISM);
+                    removed.push(ranked.remove(i).0);
+                }
+            }
+        }
+        for &u in removed.iter() {
+            solution.remove(self.ctx, &self.units, u);
+        }
+        removed
+    }
+
+    /// Dissimilarity of every candidate to the seed: the distances between
+    /// their pickup and their delivery factories and the difference of their
+    /// deadlines, each normalized by its largest value.
+    fn shaw_relatedness(&self, seed: usize, candidates: &[usize]) -> Vec<f64> {
+        let routes = self.ctx.args.static_simulator.routes();
+        let seed = &self.units[seed];
+        let components: Vec<(f64, f64)> = candidates
+            .iter()
+            .map(|&u| {
+                let unit = &self.units[u];
+                let distance = routes.query_distance(seed.pickup.clone(), unit.pickup.clone())
+                    + routes.query_distance(seed.delivery.clone(), unit.delivery.clone());
+                let time = (seed.deadline - unit.deadline).num_seconds().abs();
+                (distance as f64, time as f64)
+            })
+            .collect();
+        let max_distance = components.iter().map(|c| c.0).fold(0.0, f64::max);
+        let max_time = components.iter().map(|c| c.1).fold(0.0, f64::max);
+        let normalize = |x: f64, max: f64| if max > 0.0 { x / max } else { 0.0 };
+        components
+            .into_iter()
+            .map(|(distance, time)| normalize(distance, max_distance) + normalize(time, max_time))
+            .collect()
+    }
+
+    /// Inserts the removed units one at a time. With `k == 1` the unit with
+    /// the cheapest insertion goes first, otherwise the one with the largest
+    /// regret of not using its best vehicle compared to its next `k - 1`.
+    fn repair(&mut self, solution: &mut Solution, mut pending: Vec<usize>, k: usize) {
+        let ctx = self.ctx;
+        let insertion = |solution: &Solution, u: usize, v: usize| {
+            ctx.best_insertion(&ctx.vehicles[v], &solution.routes[v], &self.units[u])
+                .map(|(cost, stops)| (cost - solution.costs[v], stops))
+        };
+        // best insertion of every pending unit into every vehicle
+        let mut options: Vec<Vec<_>> = pending
+            .iter()
+            .map(|&u| {
+                (0..ctx.vehicles.len())
+                    .map(|v| insertion(solution, u, v))
+                    .collect()
+            })
+            .collect();
+
+        while !pending.is_empty() {
+            let mut chosen: Option<(f64, f64, usize, usize)> = None;
+            for (i, unit_options) in options.iter().enumerate() {
+                let mut deltas: Vec<(f64, usize)> = unit_options
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(v, o)| o.as_ref().map(|(delta, _)| (*delta, v)))
+                    .collect();
+                if deltas.is_empty() {
+                    continue;
+                }
+                deltas.sort_by(|a, b| a.0.total_cmp(&b.0));
+                let (best, v) = deltas[0];
+                let regret = if k <= 1 {
+                    -best
+                } else {
+                    // missing vehicles count as leaving the unit unassigned
+                    let fallback = ctx.timeout_weight * UNASSIGNED_PENALTY_HOURS;
+                    (1..k)
+                        .map(|h| deltas.get(h).map_or(fallback, |d| d.0) - best)
+                        .sum()
+                };
+                let better =
+                    chosen.is_none_or(|(r, b, _, _)| regret > r || (regret == r && best < b));
+                if better {
+                    chosen = Some((regret, best, i, v));
+                }
+            }
+            let Some((_, _, i, v)) = chosen else {
+                // nothing fits anywhere, left for a later dispatch
+                break;
+            };
+
+            let u = pending.swap_remove(i);
+            let (delta, stops) = options.swap_remove(i)[v].take().unwrap();
+            solution.routes[v] = stops;
+            solution.costs[v] += delta;
+            solution.assignment[u] = Some(v);
+            for (unit_options, &u) in options.iter_mut().zip(pending.iter()) {
+                unit_options[v] = insertion(solution, u, v);
+            }
+        }
+    }
+
+    fn run(&mut self, config: &AlnsConfig, deadline: Instant) -> Solution {
+        let ctx = self.ctx;
+        let mut current = self.initial_solution();
+        let mut current_cost = current.cost(ctx);
+        let mut best = current.clone();
+        let mut best_cost = current_cost;
+        if self.units.is_empty() {
+            return best;
+        }
+
+        let repairs: Vec<usize> = std::iter::once(1).chain(2..=config.regret_k).collect();
+        let mut destroy_weights = vec![1.0; DESTROY_OPERATORS.len()];
+        let mut repair_weights = vec![1.0; repairs.len()];
+        let mut destroy_scores = vec![(0.0, 0); DESTROY_OPERATORS.len()];
+        let mut repair_scores = vec![(0.0, 0); repairs.len()];
+        let mut temperature = -0.05 * current_cost / config.initial_acceptance.ln();
+
+        let mut iteration = 0;
+        while Instant::now() < deadline && config.max_iterations.is_none_or(|max| iteration < max) {
+            iteration += 1;
+            let d = roulette(self.rng, &destroy_weights);
+            let r = roulette(self.rng, &repair_weights);
+
+            let mut candidate = current.clone();
+            let count = self.removal_count(config, &candidate);
+            let removed = self.destroy(DESTROY_OPERATORS[d], &mut candidate, count);
+            self.repair(&mut candidate, removed, repairs[r]);
+            let cost = candidate.cost(ctx);
+
+            let score = if cost < best_cost - 1e-9 {
+                best = candidate.clone();
+                best_cost = cost;
+                Some(SCORE_BEST)
+            } else if cost < current_cost - 1e-9 {
+                Some(SCORE_BETTER)
+            } else if temperature > 0.0
+                && self.rng.random::<f64>() < ((current_cost - cost) / temperature).exp()
+            {
+                Some(SCORE_ACCEPTED)
+            } else {
+                None
+            };
+            if score.is_some() {
+                current = candidate;
+                current_cost = cost;
+            }
+            destroy_scores[d].0 += score.unwrap_or(0.0);
+            destroy_scores[d].1 += 1;
+            repair_scores[r].0 += score.unwrap_or(0.0);
+            repair_scores[r].1 += 1;
+            temperature *= config.cooling_rate;
+
+            if iteration % config.segment_length == 0 {
+                update_weights(
+                    &mut destroy_weights,
+                    &mut destroy_scores,
+                    config.reaction_factor,
+                );
+                update_weights(
+                    &mut repair_weights,
+                    &mut repair_scores,
+                    config.reaction_factor,
+                );
+            }
+        }
+        tracing::debug!("alns: {iteration} iterations, cost {best_cost:.3}");
+        best
+    }
+}
+
+fn roulette(rng: &mut SmallRng, weights: &[f64]) -> usize {
+    let mut x = rng.random::<f64>() * weights.iter().sum::<f64>();
+    for (i, w) in weights.iter().enumerate() {
+        if x < *w {
+            return i;
+        }
+        x -= w;
+    }
+    weights.len() - 1
+}
+
+fn update_weights(weights: &mut [f64], scores: &mut [(f64, usize)], reaction_factor: f64) {
+    for (weight, (score, uses)) in weights.iter_mut().zip(scores.iter_mut()) {
+        if *uses > 0 {
+            *weight = (1.0 - reaction_factor) * *weight + reaction_factor * *score / *uses as f64;
+            // keep every operator in play
+            *weight = weight.max(0.1);
+        }
+        *score = 0.0;
+        *uses = 0;
+    }
+}
+
+impl Scheduler for AlnsScheduler {
+    fn schedule(&mut self, args: SchedulerArgs) -> MapType<VehicleId, Vec<VehicleRoute>> {
+        let start = Instant::now();
+        let ctx = PlanningContext::new(&args);
+        let budget = ctx
+            .config
+            .time_interval
+            .to_std()
+            .map_or(self.config.budget, |interval| {
+                self.config.budget.min(interval.mul_f64(0.9))
+            });
+        let mut search = Search {
+            ctx: &ctx,
+            units: ctx.unallocated_units(),
+            rng: &mut self.rng,
+        };
+        let solution = search.run(&self.config, start + budget);
+        ctx.to_plan(&solution.routes)
+    }
+}
+
+#[test]
+fn test_alns_improves_insertion() {
+    use crate::testing::{self, Recorder};
+
+    let alns_config = AlnsConfig {
+        max_iterations: Some(100),
+        seed: 727,
+        ..Default::default()
+    };
+    let recorder = Recorder::default();
+    let result = testing::simulator()
+        .scheduler(Box::new(AlnsScheduler::new(alns_config.clone()).unwrap()))
+        .callback(Box::new(recorder.clone()))
+        .build()
+        .unwrap()
+        .run_to_completion()
+        .unwrap();
+    assert!(result.all_delivered());
+
+    // the search never ends worse than the insertion it starts from, and
+    // beats it in some dispatches
+    let mut improvements = 0;
+    for args in recorder.inputs().iter() {
+        let ctx = PlanningContext::new(args);
+        let mut rng = SmallRng::seed_from_u64(727);
+        let mut search = Search {
+            ctx: &ctx,
+            units: ctx.unallocated_units(),
+            rng: &mut rng,
+        };
+        let initial = search.initial_solution().cost(&ctx);
+        let improved = search
+            .run(&alns_config, Instant::now() + Duration::from_secs(60))
+            .cost(&ctx);
+        assert!(improved <= initial, "{improved} > {initial}");
+        if improved < initial - 1e-6 {
+            improvements += 1;
+        }
+    }
+    assert!(improvements > 0);
+}
+
+#[test]
+fn test_alns_destroy_without_assigned_units() {
+    use crate::testing::{self, Recorder};
+
+    let invalid = AlnsConfig {
+        initial_acceptance: 1.0,
+        ..Default::default()
+    };
+    assert!(AlnsScheduler::new(invalid).is_err());
+
+    let recorder = Recorder::default();
+    let mut sim = testing::simulator()
+        .scheduler(Box::new(InsertionScheduler))
+        .callback(Box::new(recorder.clone()))
+        .build()
+        .unwrap();
+    sim.simulate_until(sim.start_time() + chrono::Duration::hours(4))
+        .unwrap();
+    let args = recorder
+        .inputs()
+        .into_iter()
+        .find(|args| !PlanningContext::new(args).unallocated_units().is_empty())
+        .unwrap();
+
+    // as if the insertion had placed nothing
+    let ctx = PlanningContext::new(&args);
+    let mut rng = SmallRng::seed_from_u64(727);
+    let mut search = Search {
+        ctx: &ctx,
+        units: ctx.unallocated_units(),
+        rng: &mut rng,
+    };
+    let mut solution = search.initial_solution();
+    for u in 0..search.units.len() {
+        solution.remove(&ctx, &search.units, u);
+    }
+    for op in DESTROY_OPERATORS {
+        for count in [0, 1] {
+            assert!(search.destroy(op, &mut solution, count).is_empty());
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use chrono::{Duration, NaiveTime};
+use rand::{rngs::SmallRng, SeedableRng};
+
+use crate::{
+    model::{order::Order, Map},
+    simulation::scenario::ScenarioSampler,
+};
+
+use super::{
+    rollout::{candidate_plans, simulate_plan, Plan},
+    Scheduler, SchedulerArgs,
+};
+
+/// How [`AnticipatoryScheduler`] chooses among the candidates given their
+/// objective in every scenario.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    /// The candidate with the best mean objective.
+    ExpectedCost,
+    /// The candidate that is best in the most scenarios, ties broken by the
+    /// mean objective.
+    Consensus,
+}
+
+/// Parameters of [`AnticipatoryScheduler`].
+#[derive(Debug, Clone)]
+pub struct AnticipatoryConfig {
+    /// Number of candidate plans, including the one of the base policy.
+    pub num_candidates: usize,
+    /// Number of sampled scenarios every candidate is simulated in.
+    pub num_scenarios: usize,
+    /// How far ahead of the dispatch future orders are sampled.
+    pub lookahead: Duration,
+    /// How far ahead of the dispatch each candidate is simulated.
+    pub horizon: Duration,
+    pub selection: Selection,
+    pub seed: u64,
+}
+
+impl Default for AnticipatoryConfig {
+    fn default() -> Self {
+        Self {
+            num_candidates: 4,
+            num_scenarios: 4,
+            lookahead: Duration::hours(2),
+            horizon: Duration::days(1),
+            selection: Selection::ExpectedCost,
+            seed: 0,
+        }
+    }
+}
+
+/// Like [`RolloutScheduler`](super::rollout::RolloutScheduler), but simulates
+/// every candidate in several scenarios in which future orders sampled by a
+/// [`ScenarioSampler`] arrive, and picks one according to the
+/// [`Selection`].
+///
+/// The arrival rates of the sampler are scaled by how many orders have
+/// arrived so far compared to how many it expected, since the instances it
+/// learned from vary in size.
+pub struct AnticipatoryScheduler<S> {
+    base: S,
+    sampler: ScenarioSampler,
+    config: AnticipatoryConfig,
+    rng: SmallRng,
+}
+
+impl<S: Scheduler + Clone + 'static> AnticipatoryScheduler<S> {
+    pub fn new(base: S, sampler: ScenarioSampler, config: AnticipatoryConfig) -> Self {
+        let rng = SmallRng::seed_from_u64(config.seed);
+        Self {
+            base,
+            sampler,
+            config,
+            rng,
+        }
+    }
+
+    /// Samples the orders arriving after the dispatch and within the
+    /// lookahead, for each scenario.
+    fn sample_scenarios(&mut self, args: &SchedulerArgs) -> Vec<Vec<Order>> {
+        let sim = &args.static_simulator;
+        // no orders arrive after the simulated day
+        let from = args.time + Duration::seconds(1);
+        if from.date() != sim.initial_date() {
+            return vec![Vec::new(); self.config.num_scenarios];
+        }
+        let to = args.time + self.config.lookahead;
+        let to = if to.date() == sim.initial_date() {
+            to.time()
+        } else {
+            NaiveTime::from_hms_opt(23, 59, 59).unwrap()
+        };
+
+        let observed = sim.orders().keys().count() as f64;
+        let expected = self.sampler.expected_orders(NaiveTime::MIN, from.time());
+        let scale = (observed + 1.0) / (expected + 1.0);
+        (0..self.config.num_scenarios)
+            .map(|i| {
+                let prefix = format!("scenario{i}-{}-", args.time.format("%H%M%S"));
+                self.sampler
+                    .sample(&mut self.rng, from.time(), to, scale, &prefix)
+            })
+            .collect()
+    }
+}
+
+impl<S: Scheduler + Clone + 'static> Scheduler for AnticipatoryScheduler<S> {
+    fn schedule(&mut self, args: SchedulerArgs) -> Plan {
+        let base_plan = self.base.schedule(args.clone());
+        let mut candidates =
+            candidate_plans(&args, base_plan, self.config.num_candidates, &mut self.rng);
+        if candidates.len() == 1 {
+            return candidates.remove(0);
+        }
+
+        let scenarios = self.sample_scenarios(&args);
+        let scores: Vec<Vec<f64>> = candidates
+            .iter()
+            .map(|candidate| {
+                scenarios
+                    .iter()
+                    .map(|orders| {
+                        simulate_plan(
+                            &args,
+                            &self.base,
+                            candidate,
+                            self.config.horizon,
+                            orders.iter().cloned(),
+                        )
+                        .unwrap_or(f64::INFINITY)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // an infeasible base plan is rejected by the simulator as usual
+        let best = select(&scores, self.config.selection).unwrap_or(0);
+        candidates.swap_remove(best)
+    }
+}
+
+/// Index of the candidate to follow given `scores[candidate][scenario]`, or
+/// `None` if no candidate is feasible.
+fn select(scores: &[Vec<f64>], selection: Selection) -> Option<usize> {
+    let mean = |scores: &[f64]| scores.iter().sum::<f64>() / scores.len().max(1) as f64;
+    let num_scenarios = scores.first().map_or(0, Vec::len);
+    let mut wins = vec![0; scores.len()];
+    if selection == Selection::Consensus {
+        for scenario in 0..num_scenarios {
+            let best = (0..scores.len())
+                .min_by(|&a, &b| scores[a][scenario].total_cmp(&scores[b][scenario]));
+            if let Some(best) = best {
+                wins[best] += 1;
+            }
+        }
+    }
+
+    (0..scores.len())
+        .filter(|&i| scores[i].iter().all(|score| score.is_finite()))
+        .min_by(|&a, &b| {
+            wins[b]
+                .cmp(&wins[a])
+                .then(mean(&scores[a]).total_cmp(&mean(&scores[b])))
+        })
+}
+
+#[test]
+fn test_select() {
+    let scores = vec![
+        vec![10.0, 10.0, 10.0],
+        vec![9.0, 9.0, 30.0],
+        vec![f64::INFINITY, 1.0, 1.0],
+    ];
+    assert_eq!(select(&scores, Selection::ExpectedCost), Some(0));
+    assert_eq!(select(&scores, Selection::Consensus), Some(1));
+    assert_eq!(select(&scores[2..], Selection::ExpectedCost), None);
+}
+
+#[test]
+fn test_anticipatory_scheduler() {
+    use crate::{schedule::insertion::InsertionScheduler, testing};
+
+    let scheduler = AnticipatoryScheduler::new(
+        InsertionScheduler,
+        ScenarioSampler::learn(2..=9).unwrap(),
+        AnticipatoryConfig {
+            num_candidates: 3,
+            num_scenarios: 2,
+            selection: Selection::Consensus,
+            seed: 727,
+            ..Default::default()
+        },
+    );
+    let result = testing::simulator()
+        .scheduler(Box::new(scheduler))
+        .build()
+        .unwrap()
+        .run_to_completion()
+        .unwrap();
+    assert!(result.all_delivered());
+    // sampled orders stay in the forks
+    assert_eq!(result.orders.len(), 50);
+}
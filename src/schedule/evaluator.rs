@@ -0,0 +1,769 @@
+use chrono::{Duration, NaiveDateTime};
+use serde::Serialize;
+
+use crate::{
+    model::{
+        factory_info::FactoryId, order::OrderId, order_item::OrderItemId, vehicle_info::VehicleId,
+        Map as _, MapType,
+    },
+    simulation::{
+        error::SimulationError,
+        simulator::{VehiclePosition, VehicleRoute},
+    },
+};
+
+use super::SchedulerArgs;
+
+/// Projected visit of a vehicle at a factory.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StopProjection {
+    pub factory: FactoryId,
+    pub arrival: NaiveDateTime,
+    /// Expected wait for a free dock after the dock approach.
+    pub wait: Duration,
+    /// When loading starts, after the dock approach and the wait.
+    pub dock_time: NaiveDateTime,
+    pub departure: NaiveDateTime,
+    pub load_time: Duration,
+    pub unload_time: Duration,
+    /// Demand on board when leaving.
+    pub demand: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VehicleProjection {
+    /// When and where the vehicle finishes its current leg.
+    pub ready_time: NaiveDateTime,
+    pub start: FactoryId,
+    pub stops: Vec<StopProjection>,
+    pub distance: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrderProjection {
+    /// Latest projected delivery of the order's items delivered by the plan.
+    pub delivery_time: NaiveDateTime,
+    pub deadline: NaiveDateTime,
+    pub lateness: Duration,
+}
+
+/// Diagnostics and projected cost of a plan.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanEvaluation {
+    #[serde(serialize_with = "serialize_violations")]
+    pub violations: Vec<SimulationError>,
+    pub vehicles: MapType<VehicleId, VehicleProjection>,
+    pub orders: MapType<OrderId, OrderProjection>,
+    /// Distance of all projected routes, excluding the current legs.
+    pub total_distance: f32,
+    pub total_lateness: Duration,
+}
+
+fn serialize_violations<S>(violations: &[SimulationError], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.collect_seq(violations.iter().map(ToString::to_string))
+}
+
+impl PlanEvaluation {
+    pub fn is_feasible(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// Projected contribution of the plan to the competition objective.
+    pub fn competition_cost(&self, timeout_weight: f64) -> f64 {
+        timeout_weight * self.total_lateness.num_seconds() as f64 / 3600.0
+            + self.total_distance as f64 / self.vehicles.len().max(1) as f64
+    }
+}
+
+/// Earliest free time of every dock, starting from the occupancy reported in
+/// [`SchedulerArgs::factory_docks`]. Factories without a report never make
+/// vehicles wait.
+#[derive(Debug, Clone)]
+pub(crate) struct DockModel {
+    free: MapType<FactoryId, Vec<NaiveDateTime>>,
+}
+
+impl DockModel {
+    /// Docks of vehicles currently working are busy, queued and approaching
+    /// vehicles are not booked yet.
+    pub fn new(args: &SchedulerArgs) -> Self {
+        let free = args
+            .factory_docks
+            .iter()
+            .map(|(id, docks)| {
+                let mut free = docks.busy_until.clone();
+                free.resize(free.len().max(docks.num_docks.max(1) as usize), args.time);
+                (id.clone(), free)
+            })
+            .collect();
+        Self { free }
+    }
+
+    /// Like [`DockModel::new`], with the current legs of all queued and
+    /// approaching vehicles booked in order. Returns when each of these
+    /// vehicles is expected to leave the dock.
+    pub fn with_current_legs(args: &SchedulerArgs) -> (Self, MapType<VehicleId, NaiveDateTime>) {
+        let mut model = Self::new(args);
+        let mut ready = MapType::new();
+        for (factory_id, docks) in args.factory_docks.iter() {
+            for (vehicle_id, work) in docks.queue.iter() {
+                let start = model.book(factory_id, args.time, *work);
+                ready.insert(vehicle_id.clone(), start + *work);
+            }
+            for (vehicle_id, time, work) in docks.approaching.iter() {
+                let start = model.book(factory_id, *time, *work);
+                ready.insert(vehicle_id.clone(), start + *work);
+            }
+        }
+        (model, ready)
+    }
+
+    /// When a vehicle reaching the dock at `time` could start working.
+    pub fn available(&self, factory_id: &FactoryId, time: NaiveDateTime) -> NaiveDateTime {
+        self.free
+            .get(factory_id)
+            .and_then(|free| free.iter().min())
+            .map_or(time, |free| time.max(*free))
+    }
+
+    /// Occupies the first free dock for `work`, returning when it starts.
+    pub fn book(
+        &mut self,
+        factory_id: &FactoryId,
+        time: NaiveDateTime,
+        work: Duration,
+    ) -> NaiveDateTime {
+        self.book_released(factory_id, time, time, work)
+    }
+
+    /// Like [`DockModel::book`], for work that cannot start before `release`.
+    /// The dock is occupied while waiting.
+    pub fn book_released(
+        &mut self,
+        factory_id: &FactoryId,
+        time: NaiveDateTime,
+        release: NaiveDateTime,
+        work: Duration,
+    ) -> NaiveDateTime {
+        let Some(free) = self.free.get_mut(factory_id) else {
+            return time.max(release);
+        };
+        let dock = free
+            .iter_mut()
+            .min()
+            .expect("factories have at least one dock");
+        let start = time.max(*dock).max(release);
+        *dock = start + work;
+        start
+    }
+}
+
+/// Checks and projects candidate plans for a dispatch, with the rules the
+/// simulator applies once the plan is returned.
+///
+/// Vehicles start when and where they finish the leg they are currently
+/// driving or working on. Docks are assigned first come, first served across
+/// all vehicles, so the projection includes the expected waiting at
+/// congested factories.
+pub struct PlanEvaluator<'a> {
+    args: &'a SchedulerArgs,
+}
+
+/// A vehicle reaching a dock, in the order the docks are assigned.
+struct DockRequest {
+    time: NaiveDateTime,
+    seq: usize,
+    vehicle: usize,
+}
+
+impl PartialEq for DockRequest {
+    fn eq(&self, other: &Self) -> bool {
+        (self.time, self.seq) == (other.time, other.seq)
+    }
+}
+
+impl Eq for DockRequest {}
+
+impl PartialOrd for DockRequest {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DockRequest {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // BinaryHeap is a max-heap
+        (other.time, other.seq).cmp(&(self.time, self.seq))
+    }
+}
+
+/// Projection state of a single vehicle during the sweep.
+struct VehicleCursor<'r> {
+    vehicle_id: VehicleId,
+    routes: &'r [VehicleRoute],
+    /// Current leg still to be docked: factory and work duration.
+    current_leg: Option<(FactoryId, Duration)>,
+    next: usize,
+    at: FactoryId,
+    demand: i32,
+    projection: VehicleProjection,
+}
+
+impl<'a> PlanEvaluator<'a> {
+    pub fn new(args: &'a SchedulerArgs) -> Self {
+        Self { args }
+    }
+
+    /// Evaluates the plan of every vehicle. Vehicles missing from `plan` keep
+    /// their previous route, which is projected as well.
+    pub fn evaluate(&self, plan: &MapType<VehicleId, Vec<VehicleRoute>>) -> PlanEvaluation {
+        let sim = &self.args.static_simulator;
+        let mut evaluation = PlanEvaluation {
+            violations: Vec::new(),
+            vehicles: MapType::new(),
+            orders: MapType::new(),
+            total_distance: 0.0,
+            total_lateness: Duration::zero(),
+        };
+
+        for vehicle_id in plan.keys().filter(|id| !sim.vehicles().contains_key(*id)) {
+            evaluation.violations.push(SimulationError::UnknownVehicle {
+                vehicle_id: vehicle_id.clone(),
+                time: self.args.time,
+            });
+        }
+
+        let mut vehicle_routes = Vec::new();
+        // `plan_violations` checks every vehicle on its own, the first vehicle
+        // loading an item owns it
+        let mut loaded_by: MapType<OrderItemId, VehicleId> = MapType::new();
+        for vehicle_id in sim.vehicles().keys() {
+            let routes: Vec<VehicleRoute> = match plan.get(vehicle_id) {
+                Some(routes) => routes.clone(),
+                None => sim
+                    .current_route(vehicle_id)
+                    .map(|routes| routes.iter().cloned().collect())
+                    .unwrap_or_default(),
+            };
+            let mut violations = sim.plan_violations(vehicle_id, &routes, self.args.time);
+            for item_id in routes.iter().flat_map(|route| route.work.load_items.iter()) {
+                match loaded_by.get(item_id) {
+                    Some(owner) if owner != vehicle_id => {
+                        violations.push(SimulationError::ItemConflict {
+                            vehicle_id: vehicle_id.clone(),
+                            item_id: item_id.clone(),
+                            owner: owner.clone(),
+                            time: self.args.time,
+                        });
+                    }
+                    Some(_) => {}
+                    None => {
+                        loaded_by.insert(item_id.clone(), vehicle_id.clone());
+                    }
+                }
+            }
+            let unknown_items = violations
+                .iter()
+                .any(|err| matches!(err, SimulationError::UnknownItem { .. }));
+            evaluation.violations.extend(violations);
+            // such routes cannot be timed
+            if !unknown_items {
+                vehicle_routes.push((vehicle_id.clone(), routes));
+            }
+        }
+
+        for (item_id, time) in self.project(&vehicle_routes, &mut evaluation.vehicles) {
+            let deadline = self
+                .args
+                .items
+                .gets(&item_id)
+                .committed_completion_time(sim.initial_date());
+            let order =
+                evaluation
+                    .orders
+                    .entry(item_id.order_id.clone())
+                    .or_insert(OrderProjection {
+                        delivery_time: time,
+                        deadline,
+                        lateness: Duration::zero(),
+                    });
+            order.delivery_time = order.delivery_time.max(time);
+        }
+
+        evaluation.total_distance = evaluation.vehicles.values().map(|v| v.distance).sum();
+        for order in evaluation.orders.values_mut() {
+            order.lateness = (order.delivery_time - order.deadline).max(Duration::zero());
+            evaluation.total_lateness += order.lateness;
+        }
+        evaluation
+    }
+
+    /// Times the routes of all vehicles, assigning docks in the order the
+    /// vehicles reach them. Returns the projected delivery time of every
+    /// unloaded item.
+    fn project(
+        &self,
+        vehicle_routes: &[(VehicleId, Vec<VehicleRoute>)],
+        projections: &mut MapType<VehicleId, VehicleProjection>,
+    ) -> Vec<(OrderItemId, NaiveDateTime)> {
+        let sim = &self.args.static_simulator;
+        let config = sim.config();
+        let date = sim.initial_date();
+        let items = &self.args.items;
+        let demand_of =
+            |ids: &[OrderItemId]| -> i32 { ids.iter().map(|i| items.gets(i).demand).sum() };
+
+        // current legs waiting for or approaching a dock
+        let mut current_legs: MapType<&VehicleId, (FactoryId, NaiveDateTime, Duration)> =
+            MapType::new();
+        for (factory_id, docks) in self.args.factory_docks.iter() {
+            for (vehicle_id, work) in docks.queue.iter() {
+                current_legs.insert(vehicle_id, (factory_id.clone(), self.args.time, *work));
+            }
+            for (vehicle_id, time, work) in docks.approaching.iter() {
+                current_legs.insert(vehicle_id, (factory_id.clone(), *time, *work));
+            }
+        }
+
+        let mut docks = DockModel::new(self.args);
+        let mut requests = std::collections::BinaryHeap::new();
+        let mut seq = 0;
+        let mut cursors = Vec::with_capacity(vehicle_routes.len());
+        for (vehicle, (vehicle_id, routes)) in vehicle_routes.iter().enumerate() {
+            let start = match &self.args.vehicle_positions[vehicle_id] {
+                VehiclePosition::Idle(f)
+                | VehiclePosition::DoingWork(f)
+                | VehiclePosition::Transporting(_, f) => f.clone(),
+            };
+            let current_leg = current_legs.get(vehicle_id);
+            let ready_time = match current_leg {
+                // updated once the dock is assigned
+                Some((_, time, work)) => *time + *work,
+                None => sim.estimate_ready_time(vehicle_id, self.args.time),
+            };
+            let mut cursor = VehicleCursor {
+                vehicle_id: vehicle_id.clone(),
+                routes,
+                current_leg: current_leg.map(|(f, _, work)| (f.clone(), *work)),
+                next: 0,
+                at: start.clone(),
+                demand: demand_of(&self.args.vehicle_stacks[vehicle_id]),
+                projection: VehicleProjection {
+                    ready_time,
+                    start,
+                    stops: Vec::with_capacity(routes.len()),
+                    distance: 0.0,
+                },
+            };
+            let time = match current_leg {
+                Some((_, time, _)) => Some(*time),
+                None => self.next_dock_arrival(&mut cursor, ready_time),
+            };
+            if let Some(time) = time {
+                requests.push(DockRequest { time, seq, vehicle });
+                seq += 1;
+            }
+            cursors.push(cursor);
+        }
+
+        let mut deliveries = Vec::new();
+        while let Some(DockRequest { time, vehicle, .. }) = requests.pop() {
+            let cursor = &mut cursors[vehicle];
+            let departure = if let Some((factory_id, work)) = cursor.current_leg.take() {
+                let departure = docks.book(&factory_id, time, work) + work;
+                cursor.projection.ready_time = departure;
+                departure
+            } else {
+                let route = &cursor.routes[cursor.next];
+                cursor.next += 1;
+                // the simulator recomputes the work times as well
+                let load_time = config.load_time_per_box * demand_of(&route.work.load_items);
+                let unload_time = config.unload_time_per_box * demand_of(&route.work.unload_items);
+                cursor.demand += route.work.delta_demand(items);
+
+                // items not created yet are waited for at the dock
+                let release = route
+                    .work
+                    .load_items
+                    .iter()
+                    .map(|i| date.and_time(items.gets(i).creation_time))
+                    .fold(time, NaiveDateTime::max);
+                let dock_time = docks.available(&route.destination, time);
+                let start =
+                    docks.book_released(&route.destination, time, release, load_time + unload_time);
+                let departure = start + load_time + unload_time;
+                let items_unload_time: Duration = route
+                    .work
+                    .unload_items
+                    .iter()
+                    .map(|i| items.gets(i).unload_time)
+                    .sum();
+                // delivery time as recorded by the simulator, before waiting
+                // for the release
+                let delivered = dock_time + load_time + unload_time
+                    - config.dock_approaching_time
+                    - items_unload_time;
+                deliveries.extend(
+                    route
+                        .work
+                        .unload_items
+                        .iter()
+                        .map(|item_id| (item_id.clone(), delivered)),
+                );
+
+                let stop = cursor.projection.stops.last_mut().unwrap();
+                stop.wait = dock_time - time;
+                stop.dock_time = dock_time;
+                stop.departure = departure;
+                stop.load_time = load_time;
+                stop.unload_time = unload_time;
+                stop.demand = cursor.demand;
+                departure
+            };
+            if let Some(time) = self.next_dock_arrival(cursor, departure) {
+                requests.push(DockRequest { time, seq, vehicle });
+                seq += 1;
+            }
+        }
+
+        projections.extend(
+            cursors
+                .into_iter()
+                .map(|cursor| (cursor.vehicle_id, cursor.projection)),
+        );
+        deliveries
+    }
+
+    /// Drives the vehicle leaving at `time` to its next stop, returning when
+    /// it reaches the dock there.
+    fn next_dock_arrival(
+        &self,
+        cursor: &mut VehicleCursor,
+        time: NaiveDateTime,
+    ) -> Option<NaiveDateTime> {
+        let sim = &self.args.static_simulator;
+        let route = cursor.routes.get(cursor.next)?;
+        let factory = route.destination.clone();
+        let arrival = time + sim.routes().query_time(cursor.at.clone(), factory.clone());
+        cursor.projection.distance += sim
+            .routes()
+            .query_distance(cursor.at.clone(), factory.clone());
+        let dock_time = arrival + sim.config().dock_approaching_time;
+        cursor.projection.stops.push(StopProjection {
+            factory: factory.clone(),
+            arrival,
+            wait: Duration::zero(),
+            dock_time,
+            departure: dock_time,
+            load_time: Duration::zero(),
+            unload_time: Duration::zero(),
+            demand: cursor.demand,
+        });
+        cursor.at = factory;
+        Some(dock_time)
+    }
+}
+
+#[test]
+fn test_plan_evaluator() {
+    use crate::{
+        simulation::{sim_event::VehicleWork, simulator::OrderItemState},
+        testing::{self, Recorder},
+    };
+
+    use super::{insertion::InsertionScheduler, Scheduler};
+
+    type Plan = MapType<VehicleId, Vec<VehicleRoute>>;
+
+    /// Plans once there are orders, later dispatches keep every vehicle on its route.
+    #[derive(Default)]
+    struct FirstDispatch(bool);
+
+    impl Scheduler for FirstDispatch {
+        fn schedule(&mut self, args: SchedulerArgs) -> Plan {
+            let unallocated = args
+                .item_states
+                .values()
+                .any(|state| *state == OrderItemState::Unallocated);
+            if self.0 || !unallocated {
+                return MapType::new();
+            }
+            self.0 = true;
+            InsertionScheduler.schedule(args)
+        }
+    }
+
+    let recorder = Recorder::default();
+    let mut sim = testing::simulator()
+        .scheduler(Box::new(FirstDispatch::default()))
+        .callback(Box::new(recorder.clone()))
+        .build()
+        .unwrap();
+    sim.simulate_until(sim.start_time() + Duration::days(1))
+        .unwrap();
+    let result = sim.result();
+
+    let dispatches = recorder.dispatches();
+    let dispatch = dispatches.iter().find(|d| !d.output.is_empty()).unwrap();
+    let (args, plan) = (dispatch.parse(), &dispatch.output);
+    let evaluation = PlanEvaluator::new(&args).evaluate(plan);
+    assert!(evaluation.is_feasible(), "{:?}", evaluation.violations);
+    assert!(!evaluation.orders.is_empty());
+    for order in result.orders.iter() {
+        if let Some(projected) = evaluation.orders.get(&order.order_id) {
+            assert_eq!(order.deliver_time, Some(projected.delivery_time));
+            assert_eq!(order.lateness, projected.lateness);
+        }
+    }
+
+    // every violation of a broken plan is reported
+    let (vehicle_id, routes) = plan
+        .iter()
+        .find(|(_, routes)| routes.len() >= 2 && !routes[0].work.load_items.is_empty())
+        .unwrap();
+    let mut routes = routes.clone();
+    let wrong_factory = routes[1].destination.clone();
+    routes.swap(0, 1);
+    let item_id = routes[1].work.load_items[0].clone();
+    routes.push(VehicleRoute::new(
+        wrong_factory,
+        VehicleWork::new_load(&args.items, vec![item_id]),
+    ));
+    let mut broken = plan.clone();
+    broken.insert(vehicle_id.clone(), routes);
+    let violations = PlanEvaluator::new(&args).evaluate(&broken).violations;
+    assert!(violations
+        .iter()
+        .any(|err| matches!(err, SimulationError::LifoViolation { .. })));
+    assert!(violations
+        .iter()
+        .any(|err| matches!(err, SimulationError::WrongPickupFactory { .. })));
+
+    // as well as items loaded by two vehicles
+    let rival = args
+        .vehicle_positions
+        .keys()
+        .find(|id| *id != vehicle_id)
+        .unwrap();
+    let first_leg = plan[vehicle_id][0].clone();
+    let mut competing = plan.clone();
+    competing.insert(vehicle_id.clone(), vec![first_leg.clone()]);
+    competing.insert(rival.clone(), vec![first_leg.clone()]);
+    let violations = PlanEvaluator::new(&args).evaluate(&competing).violations;
+    // vehicles are checked in id order, the later one is reported
+    let (second, first) = if rival > vehicle_id {
+        (rival, vehicle_id)
+    } else {
+        (vehicle_id, rival)
+    };
+    let conflicts = first_leg
+        .work
+        .load_items
+        .iter()
+        .map(|item_id| SimulationError::ItemConflict {
+            vehicle_id: second.clone(),
+            item_id: item_id.clone(),
+            owner: first.clone(),
+            time: args.time,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(violations, conflicts);
+}
+
+#[test]
+fn test_plan_evaluator_dock_waiting() {
+    use std::fs;
+
+    use crate::{
+        simulation::{
+            sim_event::VehicleWork,
+            simulator::{InstanceSource, Simulator, VehicleInitialPosition},
+        },
+        testing::Recorder,
+    };
+
+    use super::Scheduler;
+
+    type Plan = MapType<VehicleId, Vec<VehicleRoute>>;
+
+    /// Sends every vehicle to pick up its own order at the same time.
+    #[derive(Default)]
+    struct OneOrderEach(bool);
+
+    impl Scheduler for OneOrderEach {
+        fn schedule(&mut self, args: SchedulerArgs) -> Plan {
+            if self.0 {
+                return MapType::new();
+            }
+            self.0 = true;
+            args.vehicle_stacks
+                .keys()
+                .zip(args.items.values())
+                .map(|(vehicle_id, item)| {
+                    let items = vec![item.id.clone()];
+                    let routes = vec![
+                        VehicleRoute::new(
+                            item.pickup_id.clone(),
+                            VehicleWork::new_load(&args.items, items.clone()),
+                        ),
+                        VehicleRoute::new(
+                            item.delivery_id.clone(),
+                            VehicleWork::new_unload(&args.items, items),
+                        ),
+                    ];
+                    (vehicle_id.clone(), routes)
+                })
+                .collect()
+        }
+    }
+
+    // 8 vehicles at a factory with 6 docks
+    let pickup = FactoryId("2445d4bd004c457d95957d6ecf77f759".to_string());
+    let dir = std::env::temp_dir().join(format!("dpdp_docks_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut orders = "order_id,q_standard,q_small,q_box,demand,creation_time,committed_completion_time,load_time,unload_time,pickup_id,delivery_id\n".to_string();
+    let mut vehicles = "car_num,capacity,operation_time,gps_id\n".to_string();
+    let mut positions = MapType::new();
+    for i in 1..=8 {
+        orders += &format!(
+            "010000000{i},1,0,0,1.0,00:00:00,04:00:00,240,240,{pickup},b6dd694ae05541dba369a2a759d2c2b9\n"
+        );
+        vehicles += &format!("V_{i},15,24,G_{i}\n");
+        positions.insert(VehicleId(format!("V_{i}")), pickup.clone());
+    }
+    fs::write(dir.join("orders.csv"), orders).unwrap();
+    fs::write(dir.join("vehicle_info.csv"), vehicles).unwrap();
+
+    let recorder = Recorder::default();
+    let mut sim = Simulator::builder(
+        InstanceSource::Directory(dir.clone()),
+        VehicleInitialPosition::<rand::rngs::SmallRng>::Deterministic(positions),
+    )
+    .scheduler(Box::new(OneOrderEach::default()))
+    .callback(Box::new(recorder.clone()))
+    .build()
+    .unwrap();
+    let result = sim.run_to_completion().unwrap();
+    fs::remove_dir_all(dir).unwrap();
+
+    let dispatch = &recorder.dispatches()[0];
+    let args = dispatch.parse();
+    assert_eq!(args.factory_docks[&pickup].num_docks, 6);
+    let evaluation = PlanEvaluator::new(&args).evaluate(&dispatch.output);
+    assert!(evaluation.is_feasible(), "{:?}", evaluation.violations);
+    let waiting = evaluation
+        .vehicles
+        .values()
+        .filter(|v| v.stops[0].wait > Duration::zero())
+        .count();
+    assert_eq!(waiting, 2);
+    for order in result.orders.iter() {
+        assert_eq!(
+            order.deliver_time,
+            Some(evaluation.orders[&order.order_id].delivery_time)
+        );
+    }
+}
+
+#[test]
+fn test_plan_evaluator_unloads_before_release() {
+    use std::fs;
+
+    use crate::{
+        simulation::{
+            config::SimulatorConfig,
+            sim_event::VehicleWork,
+            simulator::{InstanceSource, Simulator, VehicleInitialPosition},
+        },
+        testing::Recorder,
+    };
+
+    use super::Scheduler;
+
+    type Plan = MapType<VehicleId, Vec<VehicleRoute>>;
+
+    /// Delivers the first order and picks up the second one at the same stop.
+    #[derive(Default)]
+    struct RoundTrip(bool);
+
+    impl Scheduler for RoundTrip {
+        fn schedule(&mut self, args: SchedulerArgs) -> Plan {
+            if self.0 || args.items.keys().next().is_none() {
+                return MapType::new();
+            }
+            self.0 = true;
+            let mut items = args.items.values();
+            let (first, second) = (items.next().unwrap(), items.next().unwrap());
+            let routes = vec![
+                VehicleRoute::new(
+                    first.pickup_id.clone(),
+                    VehicleWork::new_load(&args.items, vec![first.id.clone()]),
+                ),
+                VehicleRoute::new(
+                    second.pickup_id.clone(),
+                    VehicleWork::new(&args.items, vec![second.id.clone()], vec![first.id.clone()]),
+                ),
+                VehicleRoute::new(
+                    second.delivery_id.clone(),
+                    VehicleWork::new_unload(&args.items, vec![second.id.clone()]),
+                ),
+            ];
+            [(VehicleId("V_1".to_string()), routes)]
+                .into_iter()
+                .collect()
+        }
+    }
+
+    // the second order is created long after the first one is delivered
+    let a = "2445d4bd004c457d95957d6ecf77f759";
+    let b = "b6dd694ae05541dba369a2a759d2c2b9";
+    let dir = std::env::temp_dir().join(format!("dpdp_release_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let orders = format!(
+        "order_id,q_standard,q_small,q_box,demand,creation_time,committed_completion_time,load_time,unload_time,pickup_id,delivery_id\n\
+         0100000001,1,0,0,1.0,00:00:00,04:00:00,240,240,{a},{b}\n\
+         0100000002,1,0,0,1.0,08:00:00,12:00:00,240,240,{b},{a}\n"
+    );
+    fs::write(dir.join("orders.csv"), orders).unwrap();
+    fs::write(
+        dir.join("vehicle_info.csv"),
+        "car_num,capacity,operation_time,gps_id\nV_1,15,24,G_1\n",
+    )
+    .unwrap();
+    let positions = [(VehicleId("V_1".to_string()), FactoryId(a.to_string()))]
+        .into_iter()
+        .collect();
+
+    let recorder = Recorder::default();
+    let mut sim = Simulator::builder(
+        InstanceSource::Directory(dir.clone()),
+        VehicleInitialPosition::<rand::rngs::SmallRng>::Deterministic(positions),
+    )
+    .config(SimulatorConfig {
+        clairvoyant: true,
+        ..Default::default()
+    })
+    .scheduler(Box::new(RoundTrip::default()))
+    .callback(Box::new(recorder.clone()))
+    .build()
+    .unwrap();
+    let result = sim.run_to_completion().unwrap();
+    fs::remove_dir_all(dir).unwrap();
+
+    // the first order does not wait with the loading of the second one
+    assert!(result.all_delivered());
+    assert_eq!(result.orders[0].lateness, Duration::zero());
+    let dispatches = recorder.dispatches();
+    let dispatch = dispatches.iter().find(|d| !d.output.is_empty()).unwrap();
+    let evaluation = PlanEvaluator::new(&dispatch.parse()).evaluate(&dispatch.output);
+    assert!(evaluation.is_feasible(), "{:?}", evaluation.violations);
+    for order in result.orders.iter() {
+        assert_eq!(
+            order.deliver_time,
+            Some(evaluation.orders[&order.order_id].delivery_time)
+        );
+    }
+}
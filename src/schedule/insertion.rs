@@ -0,0 +1,82 @@
+use crate::{
+    model::{vehicle_info::VehicleId, MapType},
+    simulation::simulator::VehicleRoute,
+};
+
+use super::{
+    planning::{PlanningContext, Stop, Unit},
+    Scheduler, SchedulerArgs,
+};
+
+/// Greedy cheapest insertion.
+///
+/// Starts from delivering what every vehicle has on board, then inserts the
+/// pickup and delivery of each unallocated order, earliest deadline first,
+/// where it increases the competition objective the least. Only routes that
+/// respect capacity and LIFO loading are considered. Orders that fit nowhere
+/// are left for a later dispatch.
+#[derive(Debug, Default, Clone)]
+pub struct InsertionScheduler;
+
+impl InsertionScheduler {
+    pub(crate) fn construct(ctx: &PlanningContext) -> Vec<Vec<Stop>> {
+        Self::construct_in_order(ctx, ctx.unallocated_units())
+    }
+
+    /// Inserts the units in the given order.
+    pub(crate) fn construct_in_order(ctx: &PlanningContext, units: Vec<Unit>) -> Vec<Vec<Stop>> {
+        let mut routes: Vec<_> = ctx.vehicles.iter().map(|v| ctx.initial_stops(v)).collect();
+        let mut costs: Vec<_> = ctx
+            .vehicles
+            .iter()
+            .zip(routes.iter())
+            .map(|(v, stops)| ctx.route_cost(v, stops).unwrap_or(f64::INFINITY))
+            .collect();
+
+        for unit in units {
+            let best = ctx
+                .vehicles
+                .iter()
+                .enumerate()
+                .filter_map(|(i, v)| {
+                    let (cost, stops) = ctx.best_insertion(v, &routes[i], &unit)?;
+                    Some((cost - costs[i], i, cost, stops))
+                })
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+            if let Some((_, i, cost, stops)) = best {
+                routes[i] = stops;
+                costs[i] = cost;
+            }
+        }
+        routes
+    }
+}
+
+impl Scheduler for InsertionScheduler {
+    fn schedule(&mut self, args: SchedulerArgs) -> MapType<VehicleId, Vec<VehicleRoute>> {
+        let ctx = PlanningContext::new(&args);
+        let routes = Self::construct(&ctx);
+        ctx.to_plan(&routes)
+    }
+}
+
+#[test]
+fn test_insertion_beats_naive() {
+    use crate::{simulation::config::SimulatorConfig, testing};
+
+    let config = SimulatorConfig::default();
+    let run = |scheduler: Box<dyn Scheduler>| {
+        testing::simulator()
+            .config(config.clone())
+            .scheduler(scheduler)
+            .build()
+            .unwrap()
+            .run_to_completion()
+            .unwrap()
+    };
+
+    let result = run(Box::new(InsertionScheduler));
+    assert!(result.all_delivered());
+    let naive = run(Box::new(super::naive::NaiveScheduler::new(1).unwrap()));
+    assert!(config.objective.evaluate(&result) < config.objective.evaluate(&naive));
+}
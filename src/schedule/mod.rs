@@ -1,38 +1,247 @@
+pub mod alns;
+pub mod anticipatory;
+pub mod evaluator;
+pub mod insertion;
 pub mod naive;
 pub mod noop;
+pub mod offline;
+pub mod ops;
+pub(crate) mod planning;
+pub mod remote;
+pub mod rollout;
+pub mod subprocess;
 // pub mod rl;
 
+use std::{
+    fs::{create_dir_all, File},
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
 use chrono::NaiveDateTime;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     model::{
+        deserialize_map,
+        factory_info::FactoryId,
         order_item::{OrderItemId, OrderItemMap},
+        serialize_map,
         vehicle_info::VehicleId,
         MapType,
     },
-    simulation::simulator::{OrderItemStateMap, Simulator, VehiclePosition, VehicleRoute},
+    simulation::{
+        scenario::ScenarioSampler,
+        simulator::{
+            DispatchInstance, FactoryDocks, OrderItemStateMap, Simulator, VehiclePosition,
+            VehicleRoute,
+        },
+    },
 };
 
+/// Names accepted by [`create_scheduler`].
+pub const SCHEDULER_NAMES: &[&str] = &[
+    "alns",
+    "alns:<budget>",
+    "anticipatory",
+    "insertion",
+    "naive",
+    "noop",
+    "offline",
+    "offline:<time limit>",
+    "remote:<url>",
+    "rollout",
+    "subprocess:<command>",
+];
+
+/// Creates one of the built-in schedulers by name for a benchmark instance.
+pub fn create_scheduler(name: &str, inst_num: i32) -> anyhow::Result<Box<dyn Scheduler>> {
+    if let Some(url) = name.strip_prefix("remote:") {
+        return Ok(Box::new(remote::RemoteScheduler::new(url)?));
+    }
+    if let Some(command) = name.strip_prefix("subprocess:") {
+        // one root per instance, so parallel batches do not share the
+        // exchange files
+        let command = shell_words::split(command)?;
+        return Ok(Box::new(subprocess::SubprocessScheduler::new(
+            command,
+            format!("subprocess/instance_{inst_num}"),
+        )?));
+    }
+    if let Some(time_limit) = name.strip_prefix("offline:") {
+        return Ok(Box::new(offline::OfflineScheduler::new(
+            offline::OfflineConfig {
+                time_limit: humantime::parse_duration(time_limit)?,
+                ..Default::default()
+            },
+        )));
+    }
+    if let Some(budget) = name.strip_prefix("alns:") {
+        return Ok(Box::new(alns::AlnsScheduler::new(alns::AlnsConfig {
+            budget: humantime::parse_duration(budget)?,
+            seed: inst_num as u64,
+            ..Default::default()
+        })?));
+    }
+    match name {
+        "alns" => Ok(Box::new(alns::AlnsScheduler::new(alns::AlnsConfig {
+            seed: inst_num as u64,
+            ..Default::default()
+        })?)),
+        "anticipatory" => Ok(Box::new(anticipatory::AnticipatoryScheduler::new(
+            insertion::InsertionScheduler,
+            ScenarioSampler::from_benchmark(inst_num)?,
+            anticipatory::AnticipatoryConfig {
+                seed: inst_num as u64,
+                ..Default::default()
+            },
+        ))),
+        "insertion" => Ok(Box::new(insertion::InsertionScheduler)),
+        "naive" => Ok(Box::new(naive::NaiveScheduler::new(inst_num)?)),
+        "noop" => Ok(Box::new(noop::NoopScheduler)),
+        "offline" => Ok(Box::new(offline::OfflineScheduler::new(Default::default()))),
+        "rollout" => Ok(Box::new(rollout::RolloutScheduler::new(
+            insertion::InsertionScheduler,
+            rollout::RolloutConfig {
+                seed: inst_num as u64,
+                ..Default::default()
+            },
+        ))),
+        _ => Err(anyhow::anyhow!(
+            "unknown scheduler {name}, expected one of {SCHEDULER_NAMES:?}"
+        )),
+    }
+}
+
 pub trait Scheduler {
     fn schedule(&mut self, args: SchedulerArgs) -> MapType<VehicleId, Vec<VehicleRoute>>;
+
+    /// Called by the simulator instead of [`Scheduler::schedule`]. Schedulers
+    /// that can fail, e.g. because they depend on an external service,
+    /// override it so that the failure stops the simulation with
+    /// [`SimulationError::Scheduler`].
+    ///
+    /// [`SimulationError::Scheduler`]: crate::simulation::error::SimulationError::Scheduler
+    fn try_schedule(
+        &mut self,
+        args: SchedulerArgs,
+    ) -> anyhow::Result<MapType<VehicleId, Vec<VehicleRoute>>> {
+        Ok(self.schedule(args))
+    }
+
+    /// Scheduling time to charge for the last [`Scheduler::schedule`] call if
+    /// [`SimulatorConfig::charge_scheduling_time`] is set, instead of the
+    /// measured wall time.
+    ///
+    /// [`SimulatorConfig::charge_scheduling_time`]: crate::simulation::config::SimulatorConfig::charge_scheduling_time
+    fn charged_time(&self) -> Option<std::time::Duration> {
+        None
+    }
 }
 
-#[derive(Serialize)]
+/// Input of a single dispatch.
+///
+/// Serializes to a self-contained snapshot: `static_simulator` is written as
+/// its [`DispatchInstance`] under `instance` and rebuilt from it when
+/// deserializing.
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "SchedulerArgsData")]
 pub struct SchedulerArgs {
-    #[serde(skip)]
+    #[serde(serialize_with = "serialize_map")]
     pub items: OrderItemMap,
-    #[serde(skip)]
+    #[serde(serialize_with = "serialize_map")]
     pub item_states: OrderItemStateMap,
     pub vehicle_stacks: MapType<VehicleId, Vec<OrderItemId>>,
     pub vehicle_positions: MapType<VehicleId, VehiclePosition>,
-    #[serde(skip)]
+    #[serde(rename = "instance", serialize_with = "serialize_static_simulator")]
     pub static_simulator: Simulator,
     pub time: NaiveDateTime,
     pub elapsed_distance: f32,
+    /// Dock occupancy of every factory, to anticipate waiting at congested
+    /// factories.
+    #[serde(default)]
+    pub factory_docks: MapType<FactoryId, FactoryDocks>,
 }
 
-impl SchedulerArgs {}
+impl Clone for SchedulerArgs {
+    fn clone(&self) -> Self {
+        Self {
+            items: self.items.clone(),
+            item_states: self.item_states.clone(),
+            vehicle_stacks: self.vehicle_stacks.clone(),
+            vehicle_positions: self.vehicle_positions.clone(),
+            static_simulator: self
+                .static_simulator
+                .fork(Box::new(noop::NoopScheduler), None),
+            time: self.time,
+            elapsed_distance: self.elapsed_distance,
+            factory_docks: self.factory_docks.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SchedulerArgsData {
+    #[serde(deserialize_with = "deserialize_map")]
+    items: OrderItemMap,
+    #[serde(deserialize_with = "deserialize_map")]
+    item_states: OrderItemStateMap,
+    vehicle_stacks: MapType<VehicleId, Vec<OrderItemId>>,
+    vehicle_positions: MapType<VehicleId, VehiclePosition>,
+    instance: DispatchInstance,
+    time: NaiveDateTime,
+    elapsed_distance: f32,
+    #[serde(default)]
+    factory_docks: MapType<FactoryId, FactoryDocks>,
+}
+
+impl TryFrom<SchedulerArgsData> for SchedulerArgs {
+    type Error = anyhow::Error;
+
+    fn try_from(data: SchedulerArgsData) -> Result<Self, Self::Error> {
+        let static_simulator = Simulator::from_dispatch_instance(
+            data.instance,
+            data.item_states.clone(),
+            &data.vehicle_stacks,
+            &data.vehicle_positions,
+            data.time,
+        )?;
+        Ok(Self {
+            items: data.items,
+            item_states: data.item_states,
+            vehicle_stacks: data.vehicle_stacks,
+            vehicle_positions: data.vehicle_positions,
+            static_simulator,
+            time: data.time,
+            elapsed_distance: data.elapsed_distance,
+            factory_docks: data.factory_docks,
+        })
+    }
+}
+
+fn serialize_static_simulator<S>(sim: &Simulator, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    sim.dispatch_instance().serialize(serializer)
+}
+
+impl SchedulerArgs {
+    /// Writes the snapshot as JSON, e.g. to replay a dispatch offline.
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        serde_json::to_writer(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
 
 pub fn deduplicate(plans: &mut MapType<VehicleId, Vec<VehicleRoute>>) {
     for plan in plans.values_mut() {
@@ -48,3 +257,82 @@ pub fn deduplicate(plans: &mut MapType<VehicleId, Vec<VehicleRoute>>) {
         }
     }
 }
+
+#[test]
+fn test_scheduler_args_snapshot_replay() {
+    use chrono::Duration;
+
+    use crate::testing::{self, Recorder};
+
+    let recorder = Recorder::default();
+    let mut sim = testing::simulator()
+        .callback(Box::new(recorder.clone()))
+        .build()
+        .unwrap();
+    sim.simulate_until(sim.start_time() + Duration::hours(8))
+        .unwrap();
+
+    let dispatches = recorder.dispatches();
+    let dispatch = dispatches.last().unwrap();
+    let args = dispatch.parse();
+    assert_eq!(serde_json::to_string(&args).unwrap(), dispatch.input);
+
+    let mut scheduler = naive::NaiveScheduler::from_instance(
+        args.static_simulator.vehicles().clone(),
+        args.items.clone(),
+    );
+    let replayed = scheduler.schedule(args);
+    assert_eq!(
+        serde_json::to_string(&replayed).unwrap(),
+        serde_json::to_string(&dispatch.output).unwrap()
+    );
+}
+
+#[test]
+fn test_scheduler_args_snapshot_mid_day() {
+    use chrono::Duration;
+
+    use crate::testing::{self, Recorder};
+
+    let recorder = Recorder::default();
+    let mut sim = testing::simulator()
+        .scheduler(Box::new(insertion::InsertionScheduler))
+        .callback(Box::new(recorder.clone()))
+        .build()
+        .unwrap();
+    sim.simulate_until(sim.start_time() + Duration::hours(8))
+        .unwrap();
+
+    // the last dispatch while a vehicle is loading
+    let dispatches = recorder.dispatches();
+    let dispatch = dispatches
+        .iter()
+        .rev()
+        .find(|dispatch| {
+            dispatch
+                .args
+                .vehicle_positions
+                .values()
+                .any(|p| matches!(p, VehiclePosition::DoingWork(_)))
+        })
+        .unwrap();
+    let (live, output) = (&dispatch.args, &dispatch.output);
+    let args = dispatch.parse();
+    for vehicle_id in args.vehicle_positions.keys() {
+        assert_eq!(
+            args.static_simulator
+                .estimate_ready_time(vehicle_id, args.time),
+            live.static_simulator
+                .estimate_ready_time(vehicle_id, live.time),
+        );
+    }
+
+    let evaluate =
+        |args| serde_json::to_string(&evaluator::PlanEvaluator::new(args).evaluate(output));
+    assert_eq!(evaluate(&args).unwrap(), evaluate(live).unwrap());
+    let replayed = insertion::InsertionScheduler.schedule(args);
+    assert_eq!(
+        serde_json::to_string(&replayed).unwrap(),
+        serde_json::to_string(output).unwrap()
+    );
+}
@@ -20,15 +20,22 @@ pub struct NaiveScheduler {
 
 impl NaiveScheduler {
     pub fn new(inst_num: i32) -> anyhow::Result<Self> {
-        Ok(Self {
-            vehicles: VehicleInfo::load_instance(inst_num)?,
-            order_items: Order::load_instance(inst_num)?
+        Ok(Self::from_instance(
+            VehicleInfo::load_instance(inst_num)?,
+            Order::load_instance(inst_num)?
                 .values()
                 .flat_map(Order::into_items)
                 .map(|o| (o.id.clone(), o))
                 .collect::<MapType<_, _>>()
                 .into(),
-        })
+        ))
+    }
+
+    pub fn from_instance(vehicles: VehicleInfoMap, order_items: OrderItemMap) -> Self {
+        Self {
+            vehicles,
+            order_items,
+        }
     }
 
     pub fn schedule_opt(
@@ -41,9 +48,9 @@ impl NaiveScheduler {
         }: SchedulerArgs,
         allocate: bool,
     ) -> MapType<VehicleId, Vec<VehicleRoute>> {
-        // println all items.id
+        // log all items.id
         let ids: Vec<_> = items.iter().map(|i| i.0).collect();
-        println!("items: {ids:?}");
+        tracing::debug!("items: {ids:?}");
         let mut schedule = MapType::new();
         for (vid, items) in vehicle_stacks {
             let plan: &mut Vec<VehicleRoute> = schedule.entry(vid).or_default();
@@ -0,0 +1,868 @@
+//! Exact offline solver for small instances.
+//!
+//! With every order known in advance, the routes of all vehicles are built
+//! by depth-first branch-and-bound. A node extends the route of the vehicle
+//! that finishes its last stop first, by delivering the top of its stack,
+//! picking up an order that fits or retiring the vehicle. Every set of routes
+//! is generated exactly once this way. Nodes are pruned with a lower bound on
+//! the lateness of the undelivered orders and on the distance needed to reach
+//! the factories still to be visited.
+//!
+//! An order too large for any vehicle is split greedily into parts once,
+//! before the search. The lower bound and optimality only hold for schedules
+//! under this fixed order splitting, splitting an order differently may do
+//! better.
+//!
+//! The timing mirrors the simulator, except that docks are assumed to be
+//! always available, which holds when there are no more vehicles than docks
+//! per factory. Orders cannot be loaded before their creation time, the dock
+//! stays occupied while waiting for them.
+
+use std::time::{Duration, Instant};
+
+use chrono::NaiveDateTime;
+
+use crate::{
+    model::{
+        factory_info::FactoryId, order_item::OrderItemId, vehicle_info::VehicleId, Map as _,
+        MapType,
+    },
+    simulation::{
+        config::SimulatorConfig,
+        objective::{Objective, COMPETITION_TIMEOUT_WEIGHT},
+        sim_event::VehicleWork,
+        simulator::{Simulator, VehiclePosition, VehicleRoute},
+    },
+};
+
+use super::{Scheduler, SchedulerArgs};
+
+/// Limits of [`OfflineSolver::solve`]. The search stops early with the best
+/// schedule found so far when either is reached.
+#[derive(Debug, Clone)]
+pub struct OfflineConfig {
+    pub time_limit: Duration,
+    pub node_limit: Option<u64>,
+}
+
+impl Default for OfflineConfig {
+    fn default() -> Self {
+        Self {
+            time_limit: Duration::from_secs(60),
+            node_limit: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OfflineSolution {
+    /// Routes of all vehicles from the start of the day.
+    pub plan: MapType<VehicleId, Vec<VehicleRoute>>,
+    /// Competition objective of `plan`.
+    pub objective: f64,
+    /// No schedule under the solver's fixed order splitting has a lower
+    /// objective. Equal to `objective` if the search completed.
+    pub lower_bound: f64,
+    pub nodes: u64,
+}
+
+impl OfflineSolution {
+    /// Whether `plan` is optimal under the solver's fixed order splitting.
+    pub fn is_optimal(&self) -> bool {
+        self.gap() <= 1e-9
+    }
+
+    /// Relative gap between the objective and the lower bound.
+    pub fn gap(&self) -> f64 {
+        (self.objective - self.lower_bound) / self.objective.abs().max(1.0)
+    }
+}
+
+/// Items that are picked up and delivered together: a whole order, or a part
+/// of an order too large for any vehicle.
+#[derive(Debug, Clone)]
+struct Unit {
+    order: usize,
+    items: Vec<OrderItemId>,
+    demand: i32,
+    pickup: usize,
+    delivery: usize,
+    /// Seconds since the start of the day.
+    release: i64,
+    deadline: i64,
+    /// Sum of the unload times of the items.
+    unload_time: i64,
+}
+
+#[derive(Debug, Clone)]
+struct VehicleInfo {
+    id: VehicleId,
+    capacity: i32,
+    start: usize,
+}
+
+/// A stop of a route under construction. Only the last stop of a vehicle
+/// can still be extended.
+#[derive(Debug, Clone)]
+struct RouteStop {
+    factory: usize,
+    arrival: i64,
+    /// Latest creation time of the loaded units.
+    release: i64,
+    load_demand: i32,
+    unload_demand: i32,
+    unload_time: i64,
+    /// In the order they are taken off the stack.
+    unloads: Vec<usize>,
+    loads: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct RouteState {
+    at: usize,
+    stops: Vec<RouteStop>,
+    stack: Vec<usize>,
+    demand: i32,
+    retired: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum UnitStatus {
+    Waiting,
+    OnBoard,
+    Delivered,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    routes: Vec<RouteState>,
+    status: Vec<UnitStatus>,
+    distance: f64,
+    /// Lateness in seconds of every order, over its delivered units.
+    lateness: Vec<i64>,
+    num_delivered: usize,
+}
+
+/// A step of a route in [`OfflineSolver::insertion`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step {
+    Pickup(usize),
+    Deliver(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Action {
+    Deliver,
+    /// Picks up the unit, at a new stop even if the vehicle is already at
+    /// the pickup factory if `separate`.
+    Pickup {
+        unit: usize,
+        separate: bool,
+    },
+    Retire,
+}
+
+pub struct OfflineSolver {
+    config: SimulatorConfig,
+    factories: Vec<FactoryId>,
+    time: Vec<Vec<i64>>,
+    distance: Vec<Vec<f64>>,
+    /// Shortest distance to every factory from another one.
+    min_incoming: Vec<f64>,
+    units: Vec<Unit>,
+    num_orders: usize,
+    vehicles: Vec<VehicleInfo>,
+    timeout_weight: f64,
+}
+
+impl OfflineSolver {
+    /// Prepares solving the instance of a simulator that has not started,
+    /// with all vehicles idle at their initial positions.
+    pub fn new(sim: &Simulator) -> anyhow::Result<Self> {
+        let config = sim.config().clone();
+        let date = sim.initial_date();
+        let start_time = sim.start_time();
+
+        let mut factories: Vec<FactoryId> = Vec::new();
+        let mut factory_index = |id: &FactoryId| match factories.iter().position(|f| f == id) {
+            Some(index) => index,
+            None => {
+                factories.push(id.clone());
+                factories.len() - 1
+            }
+        };
+
+        let positions = sim.vehicle_positions();
+        let mut vehicles = Vec::new();
+        for info in sim.vehicles().values() {
+            let Some(VehiclePosition::Idle(start)) = positions.get(&info.car_num) else {
+                anyhow::bail!("vehicle {} is not idle", info.car_num);
+            };
+            vehicles.push(VehicleInfo {
+                id: info.car_num.clone(),
+                capacity: info.capacity(),
+                start: factory_index(start),
+            });
+        }
+        let max_capacity = vehicles.iter().map(|v| v.capacity).max().unwrap_or(0);
+        let min_capacity = vehicles.iter().map(|v| v.capacity).min().unwrap_or(0);
+
+        let seconds = |time: NaiveDateTime| (time - start_time).num_seconds();
+        let mut units = Vec::new();
+        let orders: Vec<_> = sim.orders().values().collect();
+        for (order_index, order) in orders.iter().enumerate() {
+            let items = order.into_items();
+            let demand: i32 = items.iter().map(|i| i.demand).sum();
+            let chunk_capacity = if demand > max_capacity {
+                min_capacity
+            } else {
+                demand
+            };
+            anyhow::ensure!(
+                items.iter().all(|i| i.demand <= chunk_capacity),
+                "order {} does not fit any vehicle",
+                order.order_id
+            );
+            let mut unit: Option<Unit> = None;
+            for item in items {
+                if unit
+                    .as_ref()
+                    .is_some_and(|u| u.demand + item.demand > chunk_capacity)
+                {
+                    units.extend(unit.take());
+                }
+                let unit = unit.get_or_insert_with(|| Unit {
+                    order: order_index,
+                    items: vec![],
+                    demand: 0,
+                    pickup: factory_index(&order.pickup_id),
+                    delivery: factory_index(&order.delivery_id),
+                    release: seconds(date.and_time(order.creation_time)),
+                    deadline: seconds(order.committed_completion_time(date)),
+                    unload_time: 0,
+                });
+                unit.demand += item.demand;
+                unit.unload_time += item.unload_time.num_seconds();
+                unit.items.push(item.id);
+            }
+            units.extend(unit);
+        }
+
+        let routes = sim.routes();
+        let n = factories.len();
+        let mut time = vec![vec![0; n]; n];
+        let mut distance = vec![vec![0.0; n]; n];
+        for (i, from) in factories.iter().enumerate() {
+            for (j, to) in factories.iter().enumerate() {
+                time[i][j] = routes.query_time(from.clone(), to.clone()).num_seconds();
+                distance[i][j] = routes.query_distance(from.clone(), to.clone()) as f64;
+            }
+        }
+        let min_incoming = (0..factories.len())
+            .map(|to| {
+                (0..factories.len())
+                    .filter(|&from| from != to)
+                    .map(|from| distance[from][to])
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .map(|d| if d.is_finite() { d } else { 0.0 })
+            .collect();
+
+        let timeout_weight = match config.objective {
+            Objective::Competition { timeout_weight } => timeout_weight,
+            _ => COMPETITION_TIMEOUT_WEIGHT,
+        };
+        Ok(Self {
+            config,
+            factories,
+            time,
+            distance,
+            min_incoming,
+            units,
+            num_orders: orders.len(),
+            vehicles,
+            timeout_weight,
+        })
+    }
+
+    pub fn solve(&self, config: &OfflineConfig) -> OfflineSolution {
+        let started = Instant::now();
+        let root = self.root();
+        let mut best = [Some(self.dive(root.clone())), self.insertion()]
+            .into_iter()
+            .flatten()
+            .map(|node| (self.cost(&node), node))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .expect("diving always completes");
+        let mut nodes = 0;
+        let root_bound = self.bound(&root);
+        // children of the nodes on the current path, best last
+        let mut frames: Vec<Vec<(f64, Node)>> = vec![vec![(root_bound, root)]];
+        let mut aborted = false;
+        while let Some(frame) = frames.last_mut() {
+            let Some((bound, node)) = frame.pop() else {
+                frames.pop();
+                continue;
+            };
+            let incumbent = best.0;
+            if bound >= incumbent {
+                continue;
+            }
+            if config.node_limit.is_some_and(|limit| nodes >= limit)
+                || (nodes % 1024 == 0 && started.elapsed() >= config.time_limit)
+            {
+                frames.last_mut().unwrap().push((bound, node));
+                aborted = true;
+                break;
+            }
+            nodes += 1;
+
+            if node.num_delivered == self.units.len() {
+                best = (self.cost(&node), node);
+                continue;
+            }
+            let Some(vehicle) = self.next_vehicle(&node) else {
+                continue;
+            };
+            let mut children: Vec<_> = self
+                .children(&node, vehicle)
+                .into_iter()
+                .filter(|(bound, _)| *bound < incumbent)
+                .collect();
+            children.reverse();
+            frames.push(children);
+        }
+
+        let (objective, node) = best;
+        let lower_bound = if aborted {
+            frames
+                .iter()
+                .flatten()
+                .map(|(bound, _)| *bound)
+                .fold(objective, f64::min)
+        } else {
+            objective
+        };
+        OfflineSolution {
+            plan: self.to_plan(&node),
+            objective,
+            lower_bound,
+            nodes,
+        }
+    }
+
+    fn root(&self) -> Node {
+        Node {
+            routes: self
+                .vehicles
+                .iter()
+                .map(|v| RouteState {
+                    at: v.start,
+                    stops: vec![],
+                    stack: vec![],
+                    demand: 0,
+                    retired: false,
+                })
+                .collect(),
+            status: vec![UnitStatus::Waiting; self.units.len()],
+            distance: 0.0,
+            lateness: vec![0; self.num_orders],
+            num_delivered: 0,
+        }
+    }
+
+    /// Cheapest insertion of the units into the routes, earliest deadline
+    /// first, as an initial incumbent. `None` if some unit fits nowhere.
+    fn insertion(&self) -> Option<Node> {
+        let mut order: Vec<_> = (0..self.units.len()).collect();
+        order.sort_by_key(|&u| (self.units[u].deadline, self.units[u].release));
+
+        let mut routes: Vec<Vec<Step>> = vec![vec![]; self.vehicles.len()];
+        let mut costs = vec![0.0; self.vehicles.len()];
+        for unit in order {
+            let mut best: Option<(f64, usize, Vec<Step>)> = None;
+            for (vehicle, steps) in routes.iter().enumerate() {
+                for i in 0..=steps.len() {
+                    // the unit must be on top of the stack when delivered
+                    let mut depth = 0;
+                    for j in i..=steps.len() {
+                        if j > i {
+                            depth += match steps[j - 1] {
+                                Step::Pickup(_) => 1,
+                                Step::Deliver(_) => -1,
+                            };
+                        }
+                        if depth < 0 {
+                            break;
+                        }
+                        if depth > 0 {
+                            continue;
+                        }
+                        let mut candidate = steps.clone();
+                        candidate.insert(j, Step::Deliver(unit));
+                        candidate.insert(i, Step::Pickup(unit));
+                        let Some(node) = self.replay(&[(vehicle, &candidate)]) else {
+                            continue;
+                        };
+                        let delta = self.cost(&node) - costs[vehicle];
+                        if best.as_ref().is_none_or(|(d, _, _)| delta < *d) {
+                            best = Some((delta, vehicle, candidate));
+                        }
+                    }
+                }
+            }
+            let (delta, vehicle, steps) = best?;
+            costs[vehicle] += delta;
+            routes[vehicle] = steps;
+        }
+        let routes: Vec<_> = routes.iter().enumerate().collect();
+        self.replay(&routes)
+    }
+
+    /// Follows the steps of the given vehicles from the root, `None` if they
+    /// violate capacity or LIFO order.
+    fn replay(&self, routes: &[(usize, &Vec<Step>)]) -> Option<Node> {
+        let mut node = self.root();
+        for &(vehicle, steps) in routes {
+            for &step in steps {
+                let route = &node.routes[vehicle];
+                let action = match step {
+                    Step::Pickup(unit) => {
+                        if route.demand + self.units[unit].demand > self.vehicles[vehicle].capacity
+                        {
+                            return None;
+                        }
+                        let pickup = self.units[unit].pickup;
+                        // joining a stop is only worse if it has to wait
+                        let separate = route.stops.last().is_none_or(|stop| {
+                            route.at != pickup
+                                || self.units[unit].release > self.service_start(stop)
+                        });
+                        Action::Pickup { unit, separate }
+                    }
+                    Step::Deliver(unit) => {
+                        if route.stack.last() != Some(&unit) {
+                            return None;
+                        }
+                        Action::Deliver
+                    }
+                };
+                self.apply_mut(&mut node, vehicle, action);
+            }
+        }
+        Some(node)
+    }
+
+    /// Children of the node with their bounds, the vehicle finishing its
+    /// next stop earliest first.
+    fn children(&self, node: &Node, vehicle: usize) -> Vec<(f64, Node)> {
+        let mut children: Vec<_> = self
+            .actions(node, vehicle)
+            .into_iter()
+            .map(|action| {
+                let child = self.apply(node, vehicle, action);
+                let ready = match action {
+                    Action::Retire => i64::MAX,
+                    _ => self.ready_time(&child.routes[vehicle]),
+                };
+                (ready, self.bound(&child), child)
+            })
+            .collect();
+        children.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        children
+            .into_iter()
+            .map(|(_, bound, child)| (bound, child))
+            .collect()
+    }
+
+    /// Follows the first child down to a complete schedule. A vehicle only
+    /// retires if no waiting unit fits it, so this always succeeds.
+    fn dive(&self, mut node: Node) -> Node {
+        while node.num_delivered < self.units.len() {
+            let vehicle = self.next_vehicle(&node).expect("a vehicle fits every unit");
+            let (_, child) = self.children(&node, vehicle).swap_remove(0);
+            node = child;
+        }
+        node
+    }
+
+    fn approach(&self) -> i64 {
+        self.config.dock_approaching_time.num_seconds()
+    }
+
+    fn service_start(&self, stop: &RouteStop) -> i64 {
+        (stop.arrival + self.approach()).max(stop.release)
+    }
+
+    fn finish(&self, stop: &RouteStop) -> i64 {
+        self.service_start(stop)
+            + self.config.load_time_per_box.num_seconds() * stop.load_demand as i64
+            + self.config.unload_time_per_box.num_seconds() * stop.unload_demand as i64
+    }
+
+    /// Delivery time of the units unloaded at the stop, computed like the
+    /// simulator does when the dock is reached. Unloads do not wait for the
+    /// release of the loads.
+    fn deliver_time(&self, stop: &RouteStop) -> i64 {
+        let work = self.finish(stop) - self.service_start(stop);
+        stop.arrival + work - stop.unload_time
+    }
+
+    /// When the vehicle has finished its last stop.
+    fn ready_time(&self, route: &RouteState) -> i64 {
+        route.stops.last().map_or(0, |stop| self.finish(stop))
+    }
+
+    fn next_vehicle(&self, node: &Node) -> Option<usize> {
+        (0..self.vehicles.len())
+            .filter(|&v| !node.routes[v].retired)
+            .min_by_key(|&v| self.ready_time(&node.routes[v]))
+    }
+
+    fn actions(&self, node: &Node, vehicle: usize) -> Vec<Action> {
+        let route = &node.routes[vehicle];
+        let mut actions = Vec::new();
+        if route.stack.is_empty() {
+            actions.push(Action::Retire);
+        } else {
+            actions.push(Action::Deliver);
+        }
+        let last = route.stops.last().filter(|stop| stop.factory == route.at);
+        for (index, unit) in self.units.iter().enumerate() {
+            if node.status[index] != UnitStatus::Waiting
+                || route.demand + unit.demand > self.vehicles[vehicle].capacity
+            {
+                continue;
+            }
+            match last {
+                Some(stop) if stop.factory == unit.pickup => {
+                    actions.push(Action::Pickup {
+                        unit: index,
+                        separate: false,
+                    });
+                    // joining the stop is better unless it has to wait
+                    if unit.release > self.service_start(stop) {
+                        actions.push(Action::Pickup {
+                            unit: index,
+                            separate: true,
+                        });
+                    }
+                }
+                _ => actions.push(Action::Pickup {
+                    unit: index,
+                    separate: true,
+                }),
+            }
+        }
+        actions
+    }
+
+    fn apply(&self, node: &Node, vehicle: usize, action: Action) -> Node {
+        let mut node = node.clone();
+        self.apply_mut(&mut node, vehicle, action);
+        node
+    }
+
+    fn apply_mut(&self, node: &mut Node, vehicle: usize, action: Action) {
+        match action {
+            Action::Retire => {
+                node.routes[vehicle].retired = true;
+                return;
+            }
+            Action::Deliver => {
+                let route = &node.routes[vehicle];
+                let index = *route.stack.last().expect("stack is not empty");
+                let unit = &self.units[index];
+                let unloads_only = route.stops.last().is_some_and(|s| s.loads.is_empty());
+                self.visit(node, vehicle, unit.delivery, unloads_only);
+                let route = &mut node.routes[vehicle];
+                route.stack.pop();
+                route.demand -= unit.demand;
+                let stop = route.stops.last_mut().unwrap();
+                stop.unloads.push(index);
+                stop.unload_demand += unit.demand;
+                stop.unload_time += unit.unload_time;
+                node.status[index] = UnitStatus::Delivered;
+                node.num_delivered += 1;
+            }
+            Action::Pickup {
+                unit: index,
+                separate,
+            } => {
+                let unit = &self.units[index];
+                self.visit(node, vehicle, unit.pickup, !separate);
+                let route = &mut node.routes[vehicle];
+                route.stack.push(index);
+                route.demand += unit.demand;
+                let stop = route.stops.last_mut().unwrap();
+                stop.loads.push(index);
+                stop.load_demand += unit.demand;
+                stop.release = stop.release.max(unit.release);
+                node.status[index] = UnitStatus::OnBoard;
+            }
+        }
+
+        // the deliveries at the last stop shift with everything it does
+        let stop = node.routes[vehicle].stops.last().unwrap();
+        let deliver_time = self.deliver_time(stop);
+        for &index in &stop.unloads {
+            let unit = &self.units[index];
+            let lateness = &mut node.lateness[unit.order];
+            *lateness = (*lateness).max(deliver_time - unit.deadline);
+        }
+    }
+
+    /// Moves the vehicle to a new stop at the factory, unless it may `join`
+    /// the stop it is at.
+    fn visit(&self, node: &mut Node, vehicle: usize, factory: usize, join: bool) {
+        let ready = self.ready_time(&node.routes[vehicle]);
+        let route = &mut node.routes[vehicle];
+        if join && !route.stops.is_empty() && route.at == factory {
+            return;
+        }
+        node.distance += self.distance[route.at][factory];
+        route.stops.push(RouteStop {
+            factory,
+            arrival: ready + self.time[route.at][factory],
+            release: 0,
+            load_demand: 0,
+            unload_demand: 0,
+            unload_time: 0,
+            unloads: vec![],
+            loads: vec![],
+        });
+        route.at = factory;
+    }
+
+    fn cost(&self, node: &Node) -> f64 {
+        self.timeout_weight * node.lateness.iter().sum::<i64>() as f64 / 3600.0
+            + node.distance / self.vehicles.len().max(1) as f64
+    }
+
+    /// Lower bound on the cost of every completion of the node.
+    fn bound(&self, node: &Node) -> f64 {
+        let active: Vec<_> = node.routes.iter().filter(|r| !r.retired).collect();
+        let max_capacity = node
+            .routes
+            .iter()
+            .zip(&self.vehicles)
+            .filter(|(r, _)| !r.retired)
+            .map(|(_, v)| v.capacity)
+            .max()
+            .unwrap_or(0);
+        // earliest time a dock at the factory can be reached
+        let reach = |factory: usize| -> i64 {
+            active
+                .iter()
+                .map(|route| match route.stops.last() {
+                    Some(stop) if route.at == factory => stop.arrival,
+                    _ => self.ready_time(route) + self.time[route.at][factory],
+                })
+                .min()
+                .map_or(i64::MAX / 2, |arrival| arrival + self.approach())
+        };
+
+        let mut lateness = node.lateness.clone();
+        let mut required = vec![false; self.factories.len()];
+        for (index, unit) in self.units.iter().enumerate() {
+            // units are delivered no earlier than the arrival at the factory
+            let arrival = match node.status[index] {
+                UnitStatus::Delivered => continue,
+                UnitStatus::Waiting => {
+                    if unit.demand > max_capacity {
+                        return f64::INFINITY;
+                    }
+                    required[unit.pickup] = true;
+                    reach(unit.pickup).max(unit.release)
+                        + self.config.load_time_per_box.num_seconds() * unit.demand as i64
+                        + self.time[unit.pickup][unit.delivery]
+                }
+                UnitStatus::OnBoard => {
+                    let Some(route) = active.iter().find(|r| r.stack.contains(&index)) else {
+                        return f64::INFINITY;
+                    };
+                    match route.stops.last() {
+                        Some(stop) if route.at == unit.delivery => stop.arrival,
+                        _ => self.ready_time(route) + self.time[route.at][unit.delivery],
+                    }
+                }
+            };
+            required[unit.delivery] = true;
+            lateness[unit.order] = lateness[unit.order].max(arrival - unit.deadline);
+        }
+        for route in &active {
+            required[route.at] = false;
+        }
+        let distance: f64 = (0..self.factories.len())
+            .filter(|&f| required[f])
+            .map(|f| self.min_incoming[f])
+            .sum();
+
+        self.timeout_weight * lateness.iter().sum::<i64>() as f64 / 3600.0
+            + (node.distance + distance) / self.vehicles.len().max(1) as f64
+    }
+
+    fn to_plan(&self, node: &Node) -> MapType<VehicleId, Vec<VehicleRoute>> {
+        self.vehicles
+            .iter()
+            .zip(&node.routes)
+            .map(|(vehicle, route)| {
+                let routes = route
+                    .stops
+                    .iter()
+                    .map(|stop| {
+                        let load_items = stop
+                            .loads
+                            .iter()
+                            .flat_map(|&u| self.units[u].items.iter().cloned())
+                            .collect();
+                        // bottom to top, the last item is unloaded first
+                        let unload_items = stop
+                            .unloads
+                            .iter()
+                            .rev()
+                            .flat_map(|&u| self.units[u].items.iter().cloned())
+                            .collect();
+                        let load_time = self.config.load_time_per_box * stop.load_demand;
+                        let unload_time = self.config.unload_time_per_box * stop.unload_demand;
+                        VehicleRoute::new(
+                            self.factories[stop.factory].clone(),
+                            VehicleWork {
+                                load_items,
+                                unload_items,
+                                load_time,
+                                unload_time,
+                            },
+                        )
+                    })
+                    .collect();
+                (vehicle.id.clone(), routes)
+            })
+            .collect()
+    }
+}
+
+/// Follows an offline schedule of the whole instance, solved at the first
+/// dispatch unless given. Needs [`SimulatorConfig::clairvoyant`], since the
+/// schedule only covers the orders known at the first dispatch. Otherwise
+/// the simulation stops with [`SimulationError::Scheduler`].
+///
+/// [`SimulationError::Scheduler`]: crate::simulation::error::SimulationError::Scheduler
+pub struct OfflineScheduler {
+    config: OfflineConfig,
+    plan: Option<MapType<VehicleId, Vec<VehicleRoute>>>,
+    dispatched: bool,
+}
+
+impl OfflineScheduler {
+    pub fn new(config: OfflineConfig) -> Self {
+        Self {
+            config,
+            plan: None,
+            dispatched: false,
+        }
+    }
+
+    pub fn with_plan(plan: MapType<VehicleId, Vec<VehicleRoute>>) -> Self {
+        Self {
+            plan: Some(plan),
+            ..Self::new(OfflineConfig::default())
+        }
+    }
+
+    fn solve(&self, args: &SchedulerArgs) -> anyhow::Result<MapType<VehicleId, Vec<VehicleRoute>>> {
+        let solver = OfflineSolver::new(&args.static_simulator)?;
+        Ok(solver.solve(&self.config).plan)
+    }
+}
+
+impl Scheduler for OfflineScheduler {
+    /// Falls back to an empty plan if [`Scheduler::try_schedule`] fails.
+    fn schedule(&mut self, args: SchedulerArgs) -> MapType<VehicleId, Vec<VehicleRoute>> {
+        self.try_schedule(args).unwrap_or_else(|err| {
+            tracing::warn!("offline scheduler failed: {err:#}");
+            MapType::new()
+        })
+    }
+
+    fn try_schedule(
+        &mut self,
+        args: SchedulerArgs,
+    ) -> anyhow::Result<MapType<VehicleId, Vec<VehicleRoute>>> {
+        if !args.static_simulator.config().clairvoyant {
+            return Err(anyhow::anyhow!(
+                "the offline scheduler needs a clairvoyant simulation"
+            ));
+        }
+        if std::mem::replace(&mut self.dispatched, true) {
+            // keep following the schedule
+            return Ok(MapType::new());
+        }
+        match self.plan.take() {
+            Some(plan) => Ok(plan),
+            None => self.solve(&args),
+        }
+    }
+}
+
+#[test]
+fn test_offline_solver_beats_online() {
+    use crate::testing;
+    use chrono::NaiveTime;
+
+    let sim = testing::simulator().build().unwrap();
+    // the orders of the first hours
+    let deadline = sim
+        .initial_date()
+        .and_time(NaiveTime::from_hms_opt(2, 0, 0).unwrap());
+    let mut sim = sim.fork(
+        Box::new(super::insertion::InsertionScheduler),
+        Some(deadline),
+    );
+
+    let solution = OfflineSolver::new(&sim)
+        .unwrap()
+        .solve(&OfflineConfig::default());
+    assert!(solution.is_optimal());
+    let online = sim.run_to_completion().unwrap();
+    assert!(solution.objective <= online.objective);
+}
+
+#[test]
+fn test_offline_schedule_in_clairvoyant_simulation() {
+    use crate::{simulation::config::SimulatorConfig, testing};
+
+    let sim = testing::simulator()
+        .config(SimulatorConfig {
+            clairvoyant: true,
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+    let solution = OfflineSolver::new(&sim).unwrap().solve(&OfflineConfig {
+        node_limit: Some(1000),
+        ..Default::default()
+    });
+    assert!(solution.lower_bound <= solution.objective);
+
+    let scheduler = OfflineScheduler::with_plan(solution.plan.clone());
+    let result = sim
+        .fork(Box::new(scheduler), None)
+        .run_to_completion()
+        .unwrap();
+    assert!(result.all_delivered());
+    assert!((result.objective - solution.objective).abs() < 1e-3);
+}
+
+#[test]
+fn test_offline_scheduler_needs_clairvoyance() {
+    use crate::{simulation::error::SimulationError, testing};
+
+    let err = testing::simulator()
+        .scheduler(Box::new(OfflineScheduler::new(OfflineConfig::default())))
+        .build()
+        .unwrap()
+        .run_to_completion()
+        .unwrap_err();
+    assert!(matches!(err, SimulationError::Scheduler { .. }), "{err}");
+}
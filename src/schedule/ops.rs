@@ -0,0 +1,1148 @@
+//! Neighborhood moves for pickup and delivery routes loaded in LIFO order.
+//!
+//! A [`RouteSet`] splits the [`VehicleRoute`]s of a plan into stops that
+//! each pick up or deliver the items of one order. The pickup of items and
+//! the delivery of exactly the same items form a pair. Since the stack is
+//! LIFO, everything picked up between the two stops of a pair is delivered
+//! between them as well: the stops from a pickup to its delivery form a
+//! nested block that leaves the stack as it found it.
+//!
+//! Every [`Move`] can be checked against capacity and stack order, priced
+//! by the change in distance in O(1), and priced exactly in O(n) by timing
+//! only the routes it changes. Consecutive stops at the same factory are
+//! merged again when converting back to a plan.
+
+use chrono::Duration;
+
+use crate::{
+    model::{
+        factory_info::FactoryId, order_item::OrderItemId, vehicle_info::VehicleId, Map as _,
+        MapType,
+    },
+    simulation::simulator::VehicleRoute,
+};
+
+use super::{
+    planning::{PlanningContext, RouteCost, Stop, VehicleContext},
+    SchedulerArgs,
+};
+
+pub type Plan = MapType<VehicleId, Vec<VehicleRoute>>;
+
+/// Smallest decrease of the objective [`RouteSet::descend`] accepts, so that
+/// rounding does not make it cycle.
+const MIN_IMPROVEMENT: f64 = 1e-6;
+
+/// A change to a [`RouteSet`]. Routes are indexed like the vehicles of the
+/// simulator and pairs by the position of their pickup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Move {
+    /// Removes the pair picked up at `pickup` of route `from` and inserts it
+    /// into route `to`, so that it is picked up before the stop at `at.0`
+    /// and delivered before the stop at `at.1`. Positions are counted after
+    /// the removal and `at.0 <= at.1`.
+    RelocatePair {
+        from: usize,
+        pickup: usize,
+        to: usize,
+        at: (usize, usize),
+    },
+    /// Swaps two pairs given as `(route, pickup)`: each takes over the
+    /// positions of the other.
+    ExchangePairs {
+        first: (usize, usize),
+        second: (usize, usize),
+    },
+    /// Removes the block from the pickup at `pickup` of route `from` to its
+    /// delivery and inserts it before the stop at `at` of route `to`,
+    /// counted after the removal.
+    MoveBlock {
+        from: usize,
+        pickup: usize,
+        to: usize,
+        at: usize,
+    },
+    /// Reverses the order of the consecutive blocks that make up the stops
+    /// `start..end` of the route, keeping the order within each block.
+    ReverseBlocks {
+        route: usize,
+        start: usize,
+        end: usize,
+    },
+}
+
+impl Move {
+    /// Routes changed by the move.
+    pub fn routes(&self) -> Vec<usize> {
+        let (a, b) = match *self {
+            Move::RelocatePair { from, to, .. } | Move::MoveBlock { from, to, .. } => (from, to),
+            Move::ExchangePairs { first, second } => (first.0, second.0),
+            Move::ReverseBlocks { route, .. } => (route, route),
+        };
+        if a == b {
+            vec![a]
+        } else {
+            vec![a, b]
+        }
+    }
+}
+
+/// The routes of all vehicles, with the stack levels, pairs and cost of
+/// every route cached for evaluating moves.
+pub struct RouteSet<'a> {
+    ctx: PlanningContext<'a>,
+    routes: Vec<Vec<Stop>>,
+    /// Per stop, the position of the other stop of its pair. Deliveries of
+    /// items that were on board at the dispatch have none.
+    partners: Vec<Vec<Option<usize>>>,
+    levels: Vec<Vec<(usize, usize, i32)>>,
+    costs: Vec<RouteCost>,
+}
+
+impl<'a> RouteSet<'a> {
+    /// Splits the plan into pairs. Vehicles without an entry only deliver
+    /// what they have on board. `None` if a route is infeasible.
+    pub fn new(args: &'a SchedulerArgs, plan: &Plan) -> Option<Self> {
+        let ctx = PlanningContext::new(args);
+        let routes = ctx
+            .vehicles
+            .iter()
+            .map(|vehicle| match plan.get(&vehicle.id) {
+                Some(routes) => routes.iter().flat_map(split_route).collect(),
+                None => ctx.initial_stops(vehicle),
+            })
+            .collect();
+        Self::from_stops(ctx, routes)
+    }
+
+    fn from_stops(ctx: PlanningContext<'a>, routes: Vec<Vec<Stop>>) -> Option<Self> {
+        let costs = ctx
+            .vehicles
+            .iter()
+            .zip(&routes)
+            .map(|(vehicle, stops)| ctx.evaluate(vehicle, stops))
+            .collect::<Option<_>>()?;
+        let partners = routes.iter().map(|stops| partners(stops)).collect();
+        let levels = ctx
+            .vehicles
+            .iter()
+            .zip(&routes)
+            .map(|(vehicle, stops)| ctx.stack_levels(vehicle, stops))
+            .collect();
+        Some(Self {
+            ctx,
+            routes,
+            partners,
+            levels,
+            costs,
+        })
+    }
+
+    pub fn num_routes(&self) -> usize {
+        self.routes.len()
+    }
+
+    pub fn vehicle_id(&self, route: usize) -> &VehicleId {
+        &self.ctx.vehicles[route].id
+    }
+
+    /// Objective of the routes in the units of the competition objective.
+    pub fn cost(&self) -> f64 {
+        self.costs.iter().map(|cost| self.ctx.cost(cost)).sum()
+    }
+
+    pub fn distance(&self) -> f64 {
+        self.costs.iter().map(|cost| cost.distance as f64).sum()
+    }
+
+    pub fn lateness(&self) -> Duration {
+        self.costs.iter().map(|cost| cost.lateness).sum()
+    }
+
+    /// Positions of the pickup and delivery of every pair of the route.
+    pub fn pairs(&self, route: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.partners[route]
+            .iter()
+            .enumerate()
+            .filter_map(|(pickup, partner)| {
+                partner
+                    .filter(|&delivery| delivery > pickup)
+                    .map(|delivery| (pickup, delivery))
+            })
+    }
+
+    fn pickups(&self, route: usize) -> impl Iterator<Item = usize> + '_ {
+        self.pairs(route).map(|(pickup, _)| pickup)
+    }
+
+    /// Every relocation of a pair that respects capacity and stack order.
+    pub fn relocate_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for from in 0..self.num_routes() {
+            for (pickup, delivery) in self.pairs(from) {
+                let demand = self.demand(&self.routes[from][pickup].load);
+                let reduced = remove_positions(&self.routes[from], &[pickup, delivery]);
+                let reduced_levels = self.ctx.stack_levels(&self.ctx.vehicles[from], &reduced);
+                for to in 0..self.num_routes() {
+                    let vehicle = &self.ctx.vehicles[to];
+                    if demand > vehicle.capacity {
+                        continue;
+                    }
+                    let levels = if to == from {
+                        &reduced_levels
+                    } else {
+                        &self.levels[to]
+                    };
+                    let (initial, len) = (self.demand_before(to, 0), levels.len() - 1);
+                    for a in 0..=len {
+                        let before = if a == 0 { initial } else { levels[a - 1].2 };
+                        if before + demand > vehicle.capacity {
+                            continue;
+                        }
+                        for b in a..=len {
+                            // the pair is on top of the stack while visiting stop b - 1
+                            if b > a {
+                                let (_, lowest, load) = levels[b - 1];
+                                if lowest < levels[a].0 || load + demand > vehicle.capacity {
+                                    break;
+                                }
+                            }
+                            // and on top again when it is delivered
+                            let returned = levels[b].0 == levels[a].0;
+                            if !returned || to == from && (a, b) == (pickup, delivery - 1) {
+                                continue;
+                            }
+                            moves.push(Move::RelocatePair {
+                                from,
+                                pickup,
+                                to,
+                                at: (a, b),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    /// Every exchange of two pairs that respects capacity and stack order.
+    pub fn exchange_moves(&self) -> Vec<Move> {
+        let pairs: Vec<(usize, usize)> = (0..self.num_routes())
+            .flat_map(|route| self.pickups(route).map(move |pickup| (route, pickup)))
+            .collect();
+        let mut moves = Vec::new();
+        for (i, &first) in pairs.iter().enumerate() {
+            for &second in &pairs[i + 1..] {
+                let mv = Move::ExchangePairs { first, second };
+                // the nesting is kept, so only the capacity can be exceeded
+                let fits = if first.0 == second.0 {
+                    self.is_feasible(&mv)
+                } else {
+                    self.exchange_fits(first, second) && self.exchange_fits(second, first)
+                };
+                if fits {
+                    moves.push(mv);
+                }
+            }
+        }
+        moves
+    }
+
+    /// Whether the pair `other` fits where `pair` is carried.
+    fn exchange_fits(&self, (route, pickup): (usize, usize), other: (usize, usize)) -> bool {
+        let delivery = self.partners[route][pickup].expect("a pair");
+        let change = self.demand(&self.routes[other.0][other.1].load)
+            - self.demand(&self.routes[route][pickup].load);
+        let peak = self.levels[route][pickup..delivery]
+            .iter()
+            .map(|&(_, _, demand)| demand)
+            .max()
+            .unwrap_or(0);
+        peak + change <= self.ctx.vehicles[route].capacity
+    }
+
+    /// Every move of a block that respects capacity. Moving a block never
+    /// breaks the stack order, as it leaves the stack as it found it.
+    pub fn block_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for from in 0..self.num_routes() {
+            for (pickup, delivery) in self.pairs(from) {
+                let base = self.demand_before(from, pickup);
+                let peak = self.levels[from][pickup..=delivery]
+                    .iter()
+                    .map(|&(_, _, demand)| demand - base)
+                    .max()
+                    .unwrap_or(0);
+                let reduced = remove_range(&self.routes[from], pickup, delivery + 1);
+                let reduced_levels = self.ctx.stack_levels(&self.ctx.vehicles[from], &reduced);
+                for to in 0..self.num_routes() {
+                    let capacity = self.ctx.vehicles[to].capacity;
+                    let (levels, initial) = if to == from {
+                        (&reduced_levels, self.demand_before(from, 0))
+                    } else {
+                        (&self.levels[to], self.demand_before(to, 0))
+                    };
+                    for at in 0..levels.len() {
+                        if to == from && at == pickup {
+                            continue;
+                        }
+                        let before = if at == 0 { initial } else { levels[at - 1].2 };
+                        if before + peak <= capacity {
+                            moves.push(Move::MoveBlock {
+                                from,
+                                pickup,
+                                to,
+                                at,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    /// Every reversal of two or more consecutive blocks. Reversals never
+    /// violate capacity or stack order.
+    pub fn reversal_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for route in 0..self.num_routes() {
+            for start in self.pickups(route) {
+                let mut end = self.block_end(route, start).expect("a pair");
+                while let Some(next) = self.block_end(route, end) {
+                    end = next;
+                    moves.push(Move::ReverseBlocks { route, start, end });
+                }
+            }
+        }
+        moves
+    }
+
+    /// All moves of all neighborhoods.
+    pub fn moves(&self) -> Vec<Move> {
+        let mut moves = self.relocate_moves();
+        moves.extend(self.exchange_moves());
+        moves.extend(self.block_moves());
+        moves.extend(self.reversal_moves());
+        moves
+    }
+
+    /// Whether the routes changed by the move respect capacity and stack
+    /// order and deliver everything on board, in O(n).
+    pub fn is_feasible(&self, mv: &Move) -> bool {
+        self.candidate(mv).is_some_and(|changed| {
+            changed
+                .iter()
+                .all(|(route, stops)| self.stack_feasible(&self.ctx.vehicles[*route], stops))
+        })
+    }
+
+    /// Change in the total distance, in O(1) for all moves except reversals,
+    /// which are linear in the number of blocks. `None` if the move does not
+    /// fit the routes.
+    pub fn distance_delta(&self, mv: &Move) -> Option<f64> {
+        self.refers_to_routes(mv).then(|| match *mv {
+            Move::RelocatePair {
+                from,
+                pickup,
+                to,
+                at: (a, b),
+            } => {
+                let delivery = self.partners[from][pickup].expect("a pair");
+                let f = |k: isize| self.factory(from, k);
+                let (i, j) = (pickup as isize, delivery as isize);
+                let mut delta = if j == i + 1 {
+                    self.dist(f(i - 1), f(j + 1))
+                        - self.dist(f(i - 1), f(i))
+                        - self.dist(f(i), f(j))
+                        - self.dist(f(j), f(j + 1))
+                } else {
+                    self.dist(f(i - 1), f(i + 1))
+                        - self.dist(f(i - 1), f(i))
+                        - self.dist(f(i), f(i + 1))
+                        + self.dist(f(j - 1), f(j + 1))
+                        - self.dist(f(j - 1), f(j))
+                        - self.dist(f(j), f(j + 1))
+                };
+                let g = |k: isize| {
+                    if to != from || k < 0 {
+                        return self.factory(to, k);
+                    }
+                    let mut k = k;
+                    if k >= i {
+                        k += 1;
+                    }
+                    if k >= j {
+                        k += 1;
+                    }
+                    self.factory(from, k)
+                };
+                let (p, q) = (f(i), f(j));
+                let (a, b) = (a as isize, b as isize);
+                delta += if a == b {
+                    self.dist(g(a - 1), p) + self.dist(p, q) + self.dist(q, g(a))
+                        - self.dist(g(a - 1), g(a))
+                } else {
+                    self.dist(g(a - 1), p) + self.dist(p, g(a)) - self.dist(g(a - 1), g(a))
+                        + self.dist(g(b - 1), q)
+                        + self.dist(q, g(b))
+                        - self.dist(g(b - 1), g(b))
+                };
+                delta
+            }
+            Move::ExchangePairs { first, second } => {
+                let positions = |(route, pickup): (usize, usize)| {
+                    [pickup, self.partners[route][pickup].expect("a pair")]
+                };
+                let (p, q) = (positions(first), positions(second));
+                let with = |own: [usize; 2], other: [usize; 2], route: usize| {
+                    own.into_iter()
+                        .zip(other)
+                        .map(move |(k, o)| (k, &self.routes[route][o].factory))
+                };
+                let mut replaced: Vec<_> = with(p, q, second.0).collect();
+                replaced.extend(with(q, p, first.0));
+                if first.0 == second.0 {
+                    self.replacement_delta(first.0, &replaced)
+                } else {
+                    self.replacement_delta(first.0, &replaced[..2])
+                        + self.replacement_delta(second.0, &replaced[2..])
+                }
+            }
+            Move::MoveBlock {
+                from,
+                pickup,
+                to,
+                at,
+            } => {
+                let delivery = self.partners[from][pickup].expect("a pair");
+                let f = |k: isize| self.factory(from, k);
+                let (i, j) = (pickup as isize, delivery as isize);
+                let g = |k: isize| {
+                    if to != from || k < i {
+                        self.factory(to, k)
+                    } else {
+                        self.factory(from, k + j - i + 1)
+                    }
+                };
+                let a = at as isize;
+                self.dist(f(i - 1), f(j + 1))
+                    - self.dist(f(i - 1), f(i))
+                    - self.dist(f(j), f(j + 1))
+                    + self.dist(g(a - 1), f(i))
+                    + self.dist(f(j), g(a))
+                    - self.dist(g(a - 1), g(a))
+            }
+            Move::ReverseBlocks { route, start, end } => {
+                let f = |k: usize| self.factory(route, k as isize);
+                let before = self.factory(route, start as isize - 1);
+                let blocks = self.blocks(route, start, end).expect("consecutive blocks");
+                let (first, last) = (blocks[0], blocks[blocks.len() - 1]);
+                let mut delta = self.dist(before, f(last.0)) + self.dist(f(first.1), f(end))
+                    - self.dist(before, f(first.0))
+                    - self.dist(f(last.1), f(end));
+                for pair in blocks.windows(2) {
+                    let (left, right) = (pair[0], pair[1]);
+                    delta += self.dist(f(right.1), f(left.0)) - self.dist(f(left.1), f(right.0));
+                }
+                delta
+            }
+        })
+    }
+
+    /// Change in the objective, timing only the routes changed by the move.
+    /// `None` if the move is infeasible.
+    pub fn delta(&self, mv: &Move) -> Option<f64> {
+        let changed = self.candidate(mv)?;
+        let mut delta = 0.0;
+        for (route, stops) in &changed {
+            let cost = self.ctx.evaluate(&self.ctx.vehicles[*route], stops)?;
+            delta += self.ctx.cost(&cost) - self.ctx.cost(&self.costs[*route]);
+        }
+        Some(delta)
+    }
+
+    /// Applies the move and returns the change in the objective, or leaves
+    /// the routes unchanged and returns `None` if it is infeasible.
+    pub fn apply(&mut self, mv: &Move) -> Option<f64> {
+        let changed = self.candidate(mv)?;
+        let costs = changed
+            .iter()
+            .map(|(route, stops)| self.ctx.evaluate(&self.ctx.vehicles[*route], stops))
+            .collect::<Option<Vec<_>>>()?;
+        let mut delta = 0.0;
+        for ((route, stops), cost) in changed.into_iter().zip(costs) {
+            delta += self.ctx.cost(&cost) - self.ctx.cost(&self.costs[route]);
+            self.partners[route] = partners(&stops);
+            self.levels[route] = self.ctx.stack_levels(&self.ctx.vehicles[route], &stops);
+            self.routes[route] = stops;
+            self.costs[route] = cost;
+        }
+        Some(delta)
+    }
+
+    /// Best improvement descent over all neighborhoods. Applies at most
+    /// `max_moves` moves and returns how many were applied.
+    pub fn descend(&mut self, max_moves: usize) -> usize {
+        let mut applied = 0;
+        while applied < max_moves {
+            let best = self
+                .moves()
+                .into_iter()
+                .filter(|mv| !self.cannot_improve(mv))
+                .filter_map(|mv| Some((self.delta(&mv)?, mv)))
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+            match best {
+                Some((delta, mv)) if delta < -MIN_IMPROVEMENT => {
+                    self.apply(&mv);
+                    applied += 1;
+                }
+                _ => break,
+            }
+        }
+        applied
+    }
+
+    /// Lateness never drops below zero, so a move that changes only punctual
+    /// routes improves them only if it shortens them.
+    fn cannot_improve(&self, mv: &Move) -> bool {
+        mv.routes()
+            .iter()
+            .all(|&route| self.costs[route].lateness.is_zero())
+            && self.distance_delta(mv).is_some_and(|delta| delta >= 0.0)
+    }
+
+    pub fn to_plan(&self) -> Plan {
+        self.ctx.to_plan(&self.routes)
+    }
+
+    /// The changed routes after the move, without checking feasibility.
+    /// `None` if the move does not fit the routes.
+    fn candidate(&self, mv: &Move) -> Option<Vec<(usize, Vec<Stop>)>> {
+        if !self.refers_to_routes(mv) {
+            return None;
+        }
+        let changed = match *mv {
+            Move::RelocatePair {
+                from,
+                pickup,
+                to,
+                at: (a, b),
+            } => {
+                let delivery = self.partners[from][pickup]?;
+                let stops = &self.routes[from];
+                let reduced = remove_positions(stops, &[pickup, delivery]);
+                let target = if to == from {
+                    &reduced
+                } else {
+                    &self.routes[to]
+                };
+                let mut inserted = Vec::with_capacity(target.len() + 2);
+                inserted.extend_from_slice(&target[..a]);
+                inserted.push(stops[pickup].clone());
+                inserted.extend_from_slice(&target[a..b]);
+                inserted.push(stops[delivery].clone());
+                inserted.extend_from_slice(&target[b..]);
+                if to == from {
+                    vec![(to, inserted)]
+                } else {
+                    vec![(from, reduced), (to, inserted)]
+                }
+            }
+            Move::ExchangePairs { first, second } => {
+                let stops_of = |(route, pickup): (usize, usize)| {
+                    let delivery = self.partners[route][pickup].expect("a pair");
+                    [
+                        (pickup, &self.routes[route][pickup]),
+                        (delivery, &self.routes[route][delivery]),
+                    ]
+                };
+                let (p, q) = (stops_of(first), stops_of(second));
+                let mut routes = vec![(first.0, self.routes[first.0].clone())];
+                if second.0 != first.0 {
+                    routes.push((second.0, self.routes[second.0].clone()));
+                }
+                for ((k, _), (_, stop)) in p.into_iter().zip(q) {
+                    routes[0].1[k] = stop.clone();
+                }
+                let last = routes.len() - 1;
+                for ((k, _), (_, stop)) in q.into_iter().zip(p) {
+                    routes[last].1[k] = stop.clone();
+                }
+                routes
+            }
+            Move::MoveBlock {
+                from,
+                pickup,
+                to,
+                at,
+            } => {
+                let delivery = self.partners[from][pickup]?;
+                let stops = &self.routes[from];
+                let reduced = remove_range(stops, pickup, delivery + 1);
+                let target = if to == from {
+                    &reduced
+                } else {
+                    &self.routes[to]
+                };
+                let mut inserted = Vec::with_capacity(target.len() + delivery + 1 - pickup);
+                inserted.extend_from_slice(&target[..at]);
+                inserted.extend_from_slice(&stops[pickup..=delivery]);
+                inserted.extend_from_slice(&target[at..]);
+                if to == from {
+                    vec![(to, inserted)]
+                } else {
+                    vec![(from, reduced), (to, inserted)]
+                }
+            }
+            Move::ReverseBlocks { route, start, end } => {
+                let stops = &self.routes[route];
+                let mut reversed = stops[..start].to_vec();
+                for (first, last) in self.blocks(route, start, end)?.into_iter().rev() {
+                    reversed.extend_from_slice(&stops[first..=last]);
+                }
+                reversed.extend_from_slice(&stops[end..]);
+                vec![(route, reversed)]
+            }
+        };
+        Some(changed)
+    }
+
+    /// Whether the indices of the move refer to pairs and positions of the
+    /// current routes.
+    fn refers_to_routes(&self, mv: &Move) -> bool {
+        let is_pair = |route: usize, pickup: usize| {
+            self.partners
+                .get(route)
+                .and_then(|partners| partners.get(pickup))
+                .is_some_and(|partner| partner.is_some_and(|delivery| delivery > pickup))
+        };
+        let n = self.num_routes();
+        match *mv {
+            Move::RelocatePair {
+                from,
+                pickup,
+                to,
+                at: (a, b),
+            } => {
+                let removed = if to == from { 2 } else { 0 };
+                is_pair(from, pickup) && to < n && a <= b && b + removed <= self.routes[to].len()
+            }
+            Move::ExchangePairs { first, second } => {
+                is_pair(first.0, first.1) && is_pair(second.0, second.1) && first != second
+            }
+            Move::MoveBlock {
+                from,
+                pickup,
+                to,
+                at,
+            } => {
+                if !is_pair(from, pickup) || to >= n {
+                    return false;
+                }
+                let removed = if to == from {
+                    self.partners[from][pickup].unwrap_or(pickup) + 1 - pickup
+                } else {
+                    0
+                };
+                at + removed <= self.routes[to].len()
+            }
+            Move::ReverseBlocks { route, start, end } => {
+                route < n
+                    && self
+                        .blocks(route, start, end)
+                        .is_some_and(|blocks| blocks.len() >= 2)
+            }
+        }
+    }
+
+    /// First and last stop of the consecutive blocks that make up
+    /// `start..end`, or `None` if there are no such blocks.
+    fn blocks(&self, route: usize, start: usize, end: usize) -> Option<Vec<(usize, usize)>> {
+        let mut blocks = Vec::new();
+        let mut first = start;
+        while first < end {
+            let next = self.block_end(route, first)?;
+            blocks.push((first, next - 1));
+            first = next;
+        }
+        (first == end && !blocks.is_empty()).then_some(blocks)
+    }
+
+    /// The position after the block starting at `pickup`.
+    fn block_end(&self, route: usize, pickup: usize) -> Option<usize> {
+        let delivery = (*self.partners[route].get(pickup)?)?;
+        (delivery > pickup).then_some(delivery + 1)
+    }
+
+    /// Change in the distance of the route when the stops at the given
+    /// positions go to other factories.
+    fn replacement_delta(&self, route: usize, replaced: &[(usize, &FactoryId)]) -> f64 {
+        let old = |k: isize| self.factory(route, k);
+        let new = |k: isize| {
+            replaced
+                .iter()
+                .find(|&&(position, _)| position as isize == k)
+                .map(|&(_, factory)| factory)
+                .or_else(|| old(k))
+        };
+        let mut edges: Vec<isize> = replaced
+            .iter()
+            .flat_map(|&(k, _)| [k as isize, k as isize + 1])
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+        edges
+            .into_iter()
+            .map(|k| self.dist(new(k - 1), new(k)) - self.dist(old(k - 1), old(k)))
+            .sum()
+    }
+
+    /// Factory of the stop at position `k` of the route, the start of the
+    /// vehicle before the first stop and `None` after the last.
+    fn factory(&self, route: usize, k: isize) -> Option<&FactoryId> {
+        if k < 0 {
+            return Some(&self.ctx.vehicles[route].start);
+        }
+        self.routes[route].get(k as usize).map(|stop| &stop.factory)
+    }
+
+    /// Distance between two factories, zero past the end of a route.
+    fn dist(&self, from: Option<&FactoryId>, to: Option<&FactoryId>) -> f64 {
+        match (from, to) {
+            (Some(from), Some(to)) => {
+                let routes = self.ctx.args.static_simulator.routes();
+                routes.query_distance(from.clone(), to.clone()) as f64
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn demand(&self, items: &[OrderItemId]) -> i32 {
+        items.iter().map(|i| self.ctx.items().gets(i).demand).sum()
+    }
+
+    fn demand_before(&self, route: usize, k: usize) -> i32 {
+        match k {
+            0 => self.demand(&self.ctx.vehicles[route].stack),
+            k => self.levels[route][k - 1].2,
+        }
+    }
+
+    fn stack_feasible(&self, vehicle: &VehicleContext, stops: &[Stop]) -> bool {
+        let mut stack = vehicle.stack.clone();
+        let mut demand = self.demand(&stack);
+        for stop in stops {
+            for item_id in stop.unload.iter().rev() {
+                if stack.pop().as_ref() != Some(item_id) {
+                    return false;
+                }
+            }
+            demand += self.demand(&stop.load) - self.demand(&stop.unload);
+            if demand > vehicle.capacity {
+                return false;
+            }
+            stack.extend(stop.load.iter().cloned());
+        }
+        stack.is_empty()
+    }
+}
+
+/// Splits a stop of a plan into stops that each unload or load the items of
+/// one order, in the order the simulator handles them.
+fn split_route(route: &VehicleRoute) -> Vec<Stop> {
+    let stop = |unload: Vec<OrderItemId>, load: Vec<OrderItemId>| Stop {
+        factory: route.destination.clone(),
+        unload,
+        load,
+    };
+    let top_first: Vec<_> = route.work.unload_items.iter().rev().cloned().collect();
+    let unloads = top_first
+        .chunk_by(|a, b| a.order_id == b.order_id)
+        .map(|items| stop(items.iter().rev().cloned().collect(), vec![]));
+    let loads = route
+        .work
+        .load_items
+        .chunk_by(|a, b| a.order_id == b.order_id)
+        .map(|items| stop(vec![], items.to_vec()));
+    unloads.chain(loads).collect()
+}
+
+/// Pairs up every stop that only loads with the later stop that only
+/// unloads the same items.
+fn partners(stops: &[Stop]) -> Vec<Option<usize>> {
+    let mut partners = vec![None; stops.len()];
+    let mut pickups: MapType<&OrderItemId, usize> = MapType::new();
+    for (k, stop) in stops.iter().enumerate() {
+        if stop.unload.is_empty() {
+            if let Some(first) = stop.load.first() {
+                pickups.insert(first, k);
+            }
+        } else if let (true, Some(first)) = (stop.load.is_empty(), stop.unload.first()) {
+            if let Some(&pickup) = pickups.get(first) {
+                if stops[pickup].load == stop.unload {
+                    partners[pickup] = Some(k);
+                    partners[k] = Some(pickup);
+                }
+            }
+        }
+    }
+    partners
+}
+
+fn remove_positions(stops: &[Stop], positions: &[usize]) -> Vec<Stop> {
+    stops
+        .iter()
+        .enumerate()
+        .filter(|(k, _)| !positions.contains(k))
+        .map(|(_, stop)| stop.clone())
+        .collect()
+}
+
+fn remove_range(stops: &[Stop], start: usize, end: usize) -> Vec<Stop> {
+    let mut remaining = stops[..start].to_vec();
+    remaining.extend_from_slice(&stops[end..]);
+    remaining
+}
+
+#[test]
+fn test_moves_on_insertion_routes() {
+    use super::{evaluator::PlanEvaluator, insertion::InsertionScheduler, Scheduler};
+    use crate::testing::{self, Recorder};
+
+    let recorder = Recorder::default();
+    testing::simulator()
+        .scheduler(Box::new(InsertionScheduler))
+        .callback(Box::new(recorder.clone()))
+        .build()
+        .unwrap()
+        .run_to_completion()
+        .unwrap();
+    let args: SchedulerArgs = recorder
+        .inputs()
+        .into_iter()
+        .max_by_key(|args| PlanningContext::new(args).unallocated_units().len())
+        .unwrap();
+    let plan = InsertionScheduler.schedule(args.clone());
+    let mut routes = RouteSet::new(&args, &plan).unwrap();
+    let initial = routes.cost();
+
+    // the enumerated relocations are exactly the feasible ones
+    let relocations = routes.relocate_moves();
+    let (from, (pickup, _)) = (0..routes.num_routes())
+        .find_map(|route| Some((route, routes.pairs(route).next()?)))
+        .unwrap();
+    for to in 0..routes.num_routes() {
+        let len = routes.routes[to].len() - if to == from { 2 } else { 0 };
+        for a in 0..=len {
+            for b in a..=len {
+                let mv = Move::RelocatePair {
+                    from,
+                    pickup,
+                    to,
+                    at: (a, b),
+                };
+                let identity = routes.candidate(&mv).unwrap()[0].1 == routes.routes[from];
+                assert_eq!(
+                    relocations.contains(&mv),
+                    routes.is_feasible(&mv) && !identity,
+                    "{mv:?}"
+                );
+            }
+        }
+    }
+
+    // moving a block right behind another one makes the two reversible
+    let behind = routes.block_moves().into_iter().find(|mv| {
+        matches!(*mv, Move::MoveBlock { from, to, at, .. }
+            if from != to && at > 0 && routes.partners[to][at - 1].is_some_and(|p| p + 1 < at))
+    });
+    routes.apply(&behind.unwrap()).unwrap();
+
+    let neighborhoods = [
+        RouteSet::reversal_moves,
+        RouteSet::relocate_moves,
+        RouteSet::exchange_moves,
+        RouteSet::block_moves,
+    ];
+    for neighborhood in neighborhoods {
+        let moves = neighborhood(&routes);
+        assert!(!moves.is_empty());
+        for mv in &moves {
+            assert!(routes.is_feasible(mv), "{mv:?}");
+            let distance: f64 = routes
+                .candidate(mv)
+                .unwrap()
+                .iter()
+                .map(|(route, stops)| {
+                    let cost = routes.ctx.evaluate(&routes.ctx.vehicles[*route], stops);
+                    (cost.unwrap().distance - routes.costs[*route].distance) as f64
+                })
+                .sum();
+            let delta = routes.distance_delta(mv).unwrap();
+            assert!((distance - delta).abs() < 1e-2, "{mv:?}");
+        }
+        // the deltas match the change once applied
+        let mv = moves.iter().find(|mv| routes.delta(mv).is_some()).unwrap();
+        let (cost, distance) = (routes.cost(), routes.distance());
+        let distance_delta = routes.distance_delta(mv).unwrap();
+        let delta = routes.apply(mv).unwrap();
+        assert!((routes.cost() - cost - delta).abs() < 1e-6);
+        assert!((routes.distance() - distance - distance_delta).abs() < 1e-2);
+    }
+
+    routes.descend(20);
+    assert!(routes.cost() <= initial);
+    let evaluation = PlanEvaluator::new(&args).evaluate(&routes.to_plan());
+    assert!(evaluation.is_feasible(), "{:?}", evaluation.violations);
+}
+
+#[cfg(test)]
+const FACTORY_A: &str = "9829a9e1f6874f28b33b57a7a42bb49f";
+#[cfg(test)]
+const FACTORY_B: &str = "c1e1e4250f63479ca9261967f84b6719";
+#[cfg(test)]
+const FACTORY_C: &str = "8c0126415c904d388bfdecbb4bf23c2e";
+
+/// First dispatch of two vehicles of capacity 15 at A with four orders: 1
+/// and 2 from A to B with a demand of 6 each, 3 from A to C with 4 and 4
+/// from C to B with 10. A is 7.9 from C and 37.2 from B, B is 31.3 from C.
+#[cfg(test)]
+fn small_instance(name: &str) -> SchedulerArgs {
+    use std::fs;
+
+    use crate::{
+        schedule::noop::NoopScheduler,
+        simulation::simulator::{InstanceSource, Simulator, VehicleInitialPosition},
+        testing::Recorder,
+    };
+
+    let dir = std::env::temp_dir().join(format!("dpdp_ops_{name}_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut orders = "order_id,q_standard,q_small,q_box,demand,creation_time,committed_completion_time,load_time,unload_time,pickup_id,delivery_id\n".to_string();
+    let trips = [
+        (6, FACTORY_A, FACTORY_B),
+        (6, FACTORY_A, FACTORY_B),
+        (4, FACTORY_A, FACTORY_C),
+        (10, FACTORY_C, FACTORY_B),
+    ];
+    for (i, (demand, pickup, delivery)) in trips.into_iter().enumerate() {
+        let order = i + 1;
+        let seconds = 240 * demand;
+        orders += &format!(
+            "010000000{order},{demand},0,0,{demand}.0,00:00:00,23:00:00,{seconds},{seconds},{pickup},{delivery}\n"
+        );
+    }
+    fs::write(dir.join("orders.csv"), orders).unwrap();
+    fs::write(
+        dir.join("vehicle_info.csv"),
+        "car_num,capacity,operation_time,gps_id\nV_1,15,24,G_1\nV_2,15,24,G_2\n",
+    )
+    .unwrap();
+    let positions = ["V_1", "V_2"]
+        .into_iter()
+        .map(|id| (VehicleId(id.to_string()), FactoryId(FACTORY_A.to_string())))
+        .collect();
+
+    let recorder = Recorder::default();
+    let mut sim = Simulator::builder(
+        InstanceSource::Directory(dir.clone()),
+        VehicleInitialPosition::<rand::rngs::SmallRng>::Deterministic(positions),
+    )
+    .scheduler(Box::new(NoopScheduler))
+    .callback(Box::new(recorder.clone()))
+    .build()
+    .unwrap();
+    sim.simulate_until(sim.start_time()).unwrap();
+    fs::remove_dir_all(dir).unwrap();
+    recorder.inputs().remove(0)
+}
+
+/// A stop of [`small_plan`]: factory, unloaded and loaded orders. Orders are
+/// unloaded last one first.
+#[cfg(test)]
+type SmallStop<'a> = (&'a str, &'a [u32], &'a [u32]);
+
+/// Plan of the small instance with the routes of V_1 and V_2.
+#[cfg(test)]
+fn small_plan(args: &SchedulerArgs, routes: [&[SmallStop]; 2]) -> Plan {
+    use crate::simulation::sim_event::VehicleWork;
+
+    let items = |orders: &[u32]| -> Vec<OrderItemId> {
+        orders
+            .iter()
+            .flat_map(|order| {
+                let order_id = format!("010000000{order}");
+                args.items
+                    .keys()
+                    .filter(move |id| id.order_id.to_string() == order_id)
+                    .cloned()
+            })
+            .collect()
+    };
+    ["V_1", "V_2"]
+        .into_iter()
+        .zip(routes)
+        .map(|(vehicle_id, stops)| {
+            let routes = stops
+                .iter()
+                .map(|(factory, unload, load)| {
+                    let work = VehicleWork::new(&args.items, items(load), items(unload));
+                    VehicleRoute::new(FactoryId(factory.to_string()), work)
+                })
+                .collect();
+            (VehicleId(vehicle_id.to_string()), routes)
+        })
+        .collect()
+}
+
+/// Checks the distance delta of a feasible move against the change once
+/// applied, and that the changed routes survive a round trip through a plan.
+#[cfg(test)]
+fn assert_move_applies(args: &SchedulerArgs, routes: &mut RouteSet, mv: &Move, distance: f64) {
+    use super::evaluator::PlanEvaluator;
+
+    assert!(routes.is_feasible(mv), "{mv:?}");
+    let before = routes.distance();
+    let delta = routes.distance_delta(mv).unwrap();
+    assert!((delta - distance).abs() < 1e-3, "{delta} != {distance}");
+    routes.apply(mv).unwrap();
+    assert!((routes.distance() - before - distance).abs() < 1e-3);
+
+    let plan = routes.to_plan();
+    let evaluation = PlanEvaluator::new(args).evaluate(&plan);
+    assert!(evaluation.is_feasible(), "{:?}", evaluation.violations);
+    let reloaded = RouteSet::new(args, &plan).unwrap();
+    assert_eq!(reloaded.routes, routes.routes);
+    assert!((reloaded.cost() - routes.cost()).abs() < 1e-9);
+}
+
+/// V_1 carries 2 inside 1, V_2 delivers 3 on the way to pick up 4.
+#[cfg(test)]
+fn nested_and_chained(args: &SchedulerArgs) -> Plan {
+    let (a, b, c) = (FACTORY_A, FACTORY_B, FACTORY_C);
+    small_plan(
+        args,
+        [
+            &[(a, &[], &[1, 2]), (b, &[1, 2], &[])],
+            &[(a, &[], &[3]), (c, &[3], &[4]), (b, &[4], &[])],
+        ],
+    )
+}
+
+#[test]
+fn test_relocate_pair_moves() {
+    let args = small_instance("relocate");
+    let mut routes = RouteSet::new(&args, &nested_and_chained(&args)).unwrap();
+    assert_eq!(routes.pairs(0).collect::<Vec<_>>(), [(0, 3), (1, 2)]);
+    assert_eq!(routes.pairs(1).collect::<Vec<_>>(), [(0, 1), (2, 3)]);
+
+    // 3 delivered while 1 is on top of it, and 4 on top of 1 exceeding the capacity
+    let crossing = Move::RelocatePair {
+        from: 1,
+        pickup: 0,
+        to: 0,
+        at: (0, 1),
+    };
+    let overloaded = Move::RelocatePair {
+        from: 1,
+        pickup: 2,
+        to: 0,
+        at: (1, 1),
+    };
+    let moves = routes.relocate_moves();
+    for mv in [crossing, overloaded] {
+        assert!(!routes.is_feasible(&mv), "{mv:?}");
+        assert!(!moves.contains(&mv), "{mv:?}");
+        assert_eq!(routes.apply(&mv), None);
+    }
+
+    // V_1 goes to C and back before leaving for B, V_2 still drives to C
+    let mv = Move::RelocatePair {
+        from: 1,
+        pickup: 0,
+        to: 0,
+        at: (0, 0),
+    };
+    assert!(moves.contains(&mv));
+    assert_move_applies(&args, &mut routes, &mv, 15.8);
+}
+
+#[test]
+fn test_exchange_pairs_moves() {
+    let args = small_instance("exchange");
+    let mut routes = RouteSet::new(&args, &nested_and_chained(&args)).unwrap();
+
+    // 4 does not fit next to 2
+    let overloaded = Move::ExchangePairs {
+        first: (0, 0),
+        second: (1, 2),
+    };
+    let moves = routes.exchange_moves();
+    assert!(!routes.is_feasible(&overloaded));
+    assert!(!moves.contains(&overloaded));
+    assert_eq!(routes.apply(&overloaded), None);
+
+    // V_1 delivers 3 at C on its way to B, V_2 goes to B and back for 4
+    let mv = Move::ExchangePairs {
+        first: (0, 1),
+        second: (1, 0),
+    };
+    assert!(moves.contains(&mv));
+    assert_move_applies(&args, &mut routes, &mv, 62.6);
+}
+
+#[test]
+fn test_move_block_moves() {
+    let args = small_instance("block");
+    let mut routes = RouteSet::new(&args, &nested_and_chained(&args)).unwrap();
+
+    // 4 does not fit on top of 1
+    let overloaded = Move::MoveBlock {
+        from: 1,
+        pickup: 2,
+        to: 0,
+        at: 1,
+    };
+    let moves = routes.block_moves();
+    assert!(!routes.is_feasible(&overloaded));
+    assert!(!moves.contains(&overloaded));
+    assert_eq!(routes.apply(&overloaded), None);
+
+    // V_2 returns from C to A for 1 and 2, V_1 stays idle
+    let mv = Move::MoveBlock {
+        from: 0,
+        pickup: 0,
+        to: 1,
+        at: 2,
+    };
+    assert!(moves.contains(&mv));
+    assert_move_applies(&args, &mut routes, &mv, 39.2);
+    assert!(routes.routes[0].is_empty());
+}
+
+#[test]
+fn test_reverse_blocks_moves() {
+    let args = small_instance("reverse");
+    let mut routes = RouteSet::new(&args, &nested_and_chained(&args)).unwrap();
+
+    // 1 and 2 are nested, not consecutive
+    let nested = Move::ReverseBlocks {
+        route: 0,
+        start: 0,
+        end: 4,
+    };
+    assert!(!routes.is_feasible(&nested));
+    assert_eq!(routes.distance_delta(&nested), None);
+    assert_eq!(routes.apply(&nested), None);
+
+    // V_2 picks up 4 at C first and comes back to C with 3
+    let mv = Move::ReverseBlocks {
+        route: 1,
+        start: 0,
+        end: 4,
+    };
+    assert_eq!(routes.reversal_moves(), std::slice::from_ref(&mv));
+    assert_move_applies(&args, &mut routes, &mv, 45.1);
+}
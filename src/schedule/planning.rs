@@ -0,0 +1,414 @@
+//! Route model shared by the construction and improvement schedulers.
+//!
+//! A candidate route of a vehicle is a list of [`Stop`]s that starts where
+//! the vehicle finishes its current leg, with the items of
+//! `SchedulerArgs::vehicle_stacks` on board.
+
+use chrono::{Duration, NaiveDateTime};
+
+use crate::{
+    model::{
+        factory_info::FactoryId,
+        order::OrderId,
+        order_item::{OrderItemId, OrderItemMap},
+        vehicle_info::VehicleId,
+        Map as _, MapType,
+    },
+    simulation::{
+        config::SimulatorConfig,
+        objective::{Objective, COMPETITION_TIMEOUT_WEIGHT},
+        sim_event::VehicleWork,
+        simulator::{OrderItemState, VehiclePosition, VehicleRoute},
+    },
+};
+
+use super::{evaluator::DockModel, SchedulerArgs};
+
+/// A visit at a factory. `unload` is ordered like
+/// [`VehicleWork::unload_items`], i.e. its last item is unloaded first.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Stop {
+    pub factory: FactoryId,
+    pub unload: Vec<OrderItemId>,
+    pub load: Vec<OrderItemId>,
+}
+
+impl Stop {
+    pub fn pickup(unit: &Unit) -> Self {
+        Self {
+            factory: unit.pickup.clone(),
+            unload: vec![],
+            load: unit.items.clone(),
+        }
+    }
+
+    pub fn delivery(unit: &Unit) -> Self {
+        Self {
+            factory: unit.delivery.clone(),
+            unload: unit.items.clone(),
+            load: vec![],
+        }
+    }
+}
+
+/// Items that are always picked up and delivered together: a whole order,
+/// or a part of an order too large for any vehicle.
+#[derive(Debug, Clone)]
+pub(crate) struct Unit {
+    pub order_id: OrderId,
+    pub items: Vec<OrderItemId>,
+    pub demand: i32,
+    pub pickup: FactoryId,
+    pub delivery: FactoryId,
+    pub creation_time: NaiveDateTime,
+    pub deadline: NaiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct VehicleContext {
+    pub id: VehicleId,
+    pub capacity: i32,
+    /// Where and when the vehicle finishes its current leg.
+    pub start: FactoryId,
+    pub ready: NaiveDateTime,
+    pub stack: Vec<OrderItemId>,
+}
+
+/// Timing and cost of a candidate route.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RouteCost {
+    pub distance: f32,
+    /// Summed over the orders delivered by the route.
+    pub lateness: Duration,
+    /// Orders delivered late by the route.
+    pub late_orders: Vec<(OrderId, Duration)>,
+    pub finish: NaiveDateTime,
+}
+
+pub(crate) struct PlanningContext<'a> {
+    pub args: &'a SchedulerArgs,
+    pub config: &'a SimulatorConfig,
+    pub vehicles: Vec<VehicleContext>,
+    pub timeout_weight: f64,
+    /// Dock occupancy left by the current legs of all vehicles. Candidate
+    /// routes wait for it, but do not book docks themselves.
+    pub docks: DockModel,
+}
+
+impl<'a> PlanningContext<'a> {
+    pub fn new(args: &'a SchedulerArgs) -> Self {
+        let sim = &args.static_simulator;
+        let (docks, docked_ready) = DockModel::with_current_legs(args);
+        let vehicles = sim
+            .vehicles()
+            .values()
+            .map(|info| {
+                let start = match &args.vehicle_positions[&info.car_num] {
+                    VehiclePosition::Idle(f)
+                    | VehiclePosition::DoingWork(f)
+                    | VehiclePosition::Transporting(_, f) => f.clone(),
+                };
+                VehicleContext {
+                    id: info.car_num.clone(),
+                    capacity: info.capacity(),
+                    start,
+                    ready: docked_ready
+                        .get(&info.car_num)
+                        .copied()
+                        .unwrap_or_else(|| sim.estimate_ready_time(&info.car_num, args.time)),
+                    stack: args
+                        .vehicle_stacks
+                        .get(&info.car_num)
+                        .cloned()
+                        .unwrap_or_default(),
+                }
+            })
+            .collect();
+        let timeout_weight = match sim.config().objective {
+            Objective::Competition { timeout_weight } => timeout_weight,
+            _ => COMPETITION_TIMEOUT_WEIGHT,
+        };
+        Self {
+            args,
+            config: sim.config(),
+            vehicles,
+            timeout_weight,
+            docks,
+        }
+    }
+
+    pub fn items(&self) -> &OrderItemMap {
+        &self.args.items
+    }
+
+    /// Delivers the items on board from the top of the stack down.
+    pub fn initial_stops(&self, vehicle: &VehicleContext) -> Vec<Stop> {
+        vehicle
+            .stack
+            .iter()
+            .rev()
+            .map(|item_id| Stop {
+                factory: self.items().gets(item_id).delivery_id.clone(),
+                unload: vec![item_id.clone()],
+                load: vec![],
+            })
+            .collect()
+    }
+
+    /// Unallocated items grouped into units, earliest deadline first. Orders
+    /// larger than every vehicle are split into parts that fit the smallest.
+    pub fn unallocated_units(&self) -> Vec<Unit> {
+        let date = self.args.static_simulator.initial_date();
+        let max_capacity = self.vehicles.iter().map(|v| v.capacity).max().unwrap_or(0);
+        let min_capacity = self.vehicles.iter().map(|v| v.capacity).min().unwrap_or(0);
+
+        let mut orders: MapType<OrderId, Vec<OrderItemId>> = MapType::new();
+        for (item_id, state) in self.args.item_states.iter() {
+            if *state == OrderItemState::Unallocated {
+                orders
+                    .entry(item_id.order_id.clone())
+                    .or_default()
+                    .push(item_id.clone());
+            }
+        }
+
+        let mut units = Vec::new();
+        for (order_id, item_ids) in orders {
+            let items: Vec<_> = item_ids.iter().map(|id| self.items().gets(id)).collect();
+            let demand: i32 = items.iter().map(|i| i.demand).sum();
+            let chunk_capacity = if demand > max_capacity {
+                min_capacity
+            } else {
+                demand
+            };
+            let mut unit = None::<Unit>;
+            for item in items {
+                let unit_ref = unit.get_or_insert_with(|| Unit {
+                    order_id: order_id.clone(),
+                    items: vec![],
+                    demand: 0,
+                    pickup: item.pickup_id.clone(),
+                    delivery: item.delivery_id.clone(),
+                    creation_time: date.and_time(item.creation_time),
+                    deadline: item.committed_completion_time(date),
+                });
+                if unit_ref.demand + item.demand > chunk_capacity && !unit_ref.items.is_empty() {
+                    let full = unit.take().unwrap();
+                    unit = Some(Unit {
+                        items: vec![],
+                        demand: 0,
+                        ..full.clone()
+                    });
+                    units.push(full);
+                }
+                let unit_ref = unit.as_mut().unwrap();
+                unit_ref.items.push(item.id.clone());
+                unit_ref.demand += item.demand;
+            }
+            units.extend(unit);
+        }
+        units.sort_by_key(|u| (u.deadline, u.creation_time));
+        units
+    }
+
+    /// Simulates the route like the simulator would, waiting for docks
+    /// occupied by other vehicles' current legs.
+    /// Returns `None` if it violates capacity or LIFO order, or does not
+    /// deliver everything on board.
+    pub fn evaluate(&self, vehicle: &VehicleContext, stops: &[Stop]) -> Option<RouteCost> {
+        let routes = self.args.static_simulator.routes();
+        let date = self.args.static_simulator.initial_date();
+        let mut stack = vehicle.stack.clone();
+        let mut demand: i32 = stack.iter().map(|i| self.items().gets(i).demand).sum();
+        let mut time = vehicle.ready;
+        let mut at = &vehicle.start;
+        let mut distance = 0.0;
+        let mut delivered: MapType<&OrderId, (NaiveDateTime, NaiveDateTime)> = MapType::new();
+
+        let stops = merge_stops(stops);
+        for stop in stops.iter() {
+            time += routes.query_time(at.clone(), stop.factory.clone());
+            distance += routes.query_distance(at.clone(), stop.factory.clone());
+            at = &stop.factory;
+
+            let mut unload_demand = 0;
+            let mut unload_time = Duration::zero();
+            for item_id in stop.unload.iter().rev() {
+                if stack.pop().as_ref() != Some(item_id) {
+                    return None;
+                }
+                let item = self.items().gets(item_id);
+                unload_demand += item.demand;
+                unload_time += item.unload_time;
+            }
+            let load_demand: i32 = stop.load.iter().map(|i| self.items().gets(i).demand).sum();
+            demand += load_demand - unload_demand;
+            if demand > vehicle.capacity {
+                return None;
+            }
+            stack.extend(stop.load.iter().cloned());
+
+            // items not created yet are waited for
+            let release = stop
+                .load
+                .iter()
+                .map(|i| date.and_time(self.items().gets(i).creation_time))
+                .fold(NaiveDateTime::MIN, NaiveDateTime::max);
+            let dock_time = self
+                .docks
+                .available(&stop.factory, time + self.config.dock_approaching_time);
+            let work = self.config.load_time_per_box * load_demand
+                + self.config.unload_time_per_box * unload_demand;
+            time = dock_time.max(release) + work;
+            // same bookkeeping as the simulator, unloads do not wait for the release
+            let deliver_time = dock_time + work - self.config.dock_approaching_time - unload_time;
+            for item_id in stop.unload.iter() {
+                let deadline = self.items().gets(item_id).committed_completion_time(date);
+                let entry = delivered
+                    .entry(&item_id.order_id)
+                    .or_insert((deliver_time, deadline));
+                entry.0 = entry.0.max(deliver_time);
+            }
+        }
+        if !stack.is_empty() {
+            return None;
+        }
+
+        let late_orders: Vec<_> = delivered
+            .into_iter()
+            .map(|(order_id, (deliver_time, deadline))| (order_id, deliver_time - deadline))
+            .filter(|(_, lateness)| *lateness > Duration::zero())
+            .map(|(order_id, lateness)| (order_id.clone(), lateness))
+            .collect();
+        Some(RouteCost {
+            distance,
+            lateness: late_orders.iter().map(|(_, lateness)| *lateness).sum(),
+            late_orders,
+            finish: time,
+        })
+    }
+
+    /// Scalar cost in the units of the competition objective.
+    pub fn cost(&self, cost: &RouteCost) -> f64 {
+        self.timeout_weight * cost.lateness.num_seconds() as f64 / 3600.0
+            + cost.distance as f64 / self.vehicles.len().max(1) as f64
+    }
+
+    pub fn route_cost(&self, vehicle: &VehicleContext, stops: &[Stop]) -> Option<f64> {
+        self.evaluate(vehicle, stops).map(|c| self.cost(&c))
+    }
+
+    /// Per stop, the number of items on board before it, the lowest number
+    /// while unloading at it and the demand on board after it, followed by
+    /// the state at the end of the route.
+    pub fn stack_levels(
+        &self,
+        vehicle: &VehicleContext,
+        stops: &[Stop],
+    ) -> Vec<(usize, usize, i32)> {
+        let demand_of = |items: &[OrderItemId]| -> i32 {
+            items.iter().map(|i| self.items().gets(i).demand).sum()
+        };
+        let mut height = vehicle.stack.len();
+        let mut demand = demand_of(&vehicle.stack);
+        let mut levels = Vec::with_capacity(stops.len() + 1);
+        for stop in stops {
+            let lowest = height.saturating_sub(stop.unload.len());
+            demand += demand_of(&stop.load) - demand_of(&stop.unload);
+            levels.push((height, lowest, demand));
+            height = lowest + stop.load.len();
+        }
+        levels.push((height, height, demand));
+        levels
+    }
+
+    /// Cheapest feasible insertion of the unit's pickup and delivery into
+    /// the route, as the new cost and route.
+    pub fn best_insertion(
+        &self,
+        vehicle: &VehicleContext,
+        stops: &[Stop],
+        unit: &Unit,
+    ) -> Option<(f64, Vec<Stop>)> {
+        if unit.demand > vehicle.capacity {
+            return None;
+        }
+        let levels = self.stack_levels(vehicle, stops);
+        let mut best: Option<(f64, Vec<Stop>)> = None;
+        for i in 0..=stops.len() {
+            for j in i..=stops.len() {
+                if j > i {
+                    // the unit is on top of the stack while visiting stop j - 1
+                    let (_, lowest, demand) = levels[j - 1];
+                    if lowest < levels[i].0 || demand + unit.demand > vehicle.capacity {
+                        break;
+                    }
+                }
+                let mut candidate = Vec::with_capacity(stops.len() + 2);
+                candidate.extend_from_slice(&stops[..i]);
+                candidate.push(Stop::pickup(unit));
+                candidate.extend_from_slice(&stops[i..j]);
+                candidate.push(Stop::delivery(unit));
+                candidate.extend_from_slice(&stops[j..]);
+                if let Some(cost) = self.route_cost(vehicle, &candidate) {
+                    if best.as_ref().is_none_or(|(c, _)| cost < *c) {
+                        best = Some((cost, candidate));
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    /// Converts one route per vehicle, in the order of `self.vehicles`, into
+    /// a plan. Every vehicle gets an entry, so previous plans are replaced.
+    pub fn to_plan(&self, routes: &[Vec<Stop>]) -> MapType<VehicleId, Vec<VehicleRoute>> {
+        self.vehicles
+            .iter()
+            .zip(routes)
+            .map(|(vehicle, stops)| {
+                let routes = merge_stops(stops)
+                    .into_iter()
+                    .map(|stop| {
+                        VehicleRoute::new(
+                            stop.factory,
+                            VehicleWork::with_config(
+                                self.items(),
+                                stop.load,
+                                stop.unload,
+                                self.config,
+                            ),
+                        )
+                    })
+                    .collect();
+                (vehicle.id.clone(), routes)
+            })
+            .collect()
+    }
+}
+
+/// Merges consecutive stops at the same factory where the simulator's
+/// unload-then-load order keeps the stack operations unchanged, saving the
+/// dock approach.
+pub(crate) fn merge_stops(stops: &[Stop]) -> Vec<Stop> {
+    let mut merged: Vec<Stop> = Vec::with_capacity(stops.len());
+    for stop in stops {
+        if let Some(last) = merged.last_mut() {
+            if last.factory == stop.factory {
+                if last.load.is_empty() {
+                    let mut unload = stop.unload.clone();
+                    unload.append(&mut last.unload);
+                    last.unload = unload;
+                    last.load = stop.load.clone();
+                    continue;
+                }
+                if stop.unload.is_empty() {
+                    last.load.extend(stop.load.iter().cloned());
+                    continue;
+                }
+            }
+        }
+        merged.push(stop.clone());
+    }
+    merged
+}
@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+use reqwest::blocking::Client;
+
+use crate::{
+    model::{vehicle_info::VehicleId, MapType},
+    simulation::simulator::VehicleRoute,
+};
+
+use super::{Scheduler, SchedulerArgs};
+
+/// Delegates dispatching to a web service.
+///
+/// Every dispatch POSTs the serialized [`SchedulerArgs`] as JSON to `url` and
+/// expects a JSON object mapping vehicle IDs to their new routes in return.
+/// `load_time` and `unload_time` of the returned works may be omitted.
+pub struct RemoteScheduler {
+    client: Client,
+    url: String,
+}
+
+impl RemoteScheduler {
+    pub fn new(url: impl Into<String>) -> anyhow::Result<Self> {
+        Self::with_timeout(url, Duration::from_secs(600))
+    }
+
+    pub fn with_timeout(url: impl Into<String>, timeout: Duration) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: Client::builder().timeout(timeout).build()?,
+            url: url.into(),
+        })
+    }
+}
+
+impl Scheduler for RemoteScheduler {
+    /// Falls back to an empty plan if the request fails, so every vehicle
+    /// keeps following its previous route. Only used outside the simulator,
+    /// which stops with an error instead.
+    fn schedule(&mut self, args: SchedulerArgs) -> MapType<VehicleId, Vec<VehicleRoute>> {
+        self.try_schedule(args).unwrap_or_else(|err| {
+            tracing::warn!("remote scheduler at {} failed: {err:#}", self.url);
+            MapType::new()
+        })
+    }
+
+    fn try_schedule(
+        &mut self,
+        args: SchedulerArgs,
+    ) -> anyhow::Result<MapType<VehicleId, Vec<VehicleRoute>>> {
+        Ok(self
+            .client
+            .post(&self.url)
+            .json(&args)
+            .send()?
+            .error_for_status()?
+            .json()?)
+    }
+}
+
+#[test]
+fn test_remote_scheduler_round_trip() {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+    };
+
+    use chrono::Duration;
+    use serde_json::{json, Value};
+
+    use crate::testing::{self, Recorder};
+
+    // stand-in server: load the first unallocated item onto V_1
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/dispatch", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let mut requests = 0;
+        for stream in listener.incoming().take(2) {
+            let mut reader = BufReader::new(stream.unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let args: Value = serde_json::from_slice(&body).unwrap();
+
+            let plan = args["item_states"]
+                .as_object()
+                .unwrap()
+                .iter()
+                .find(|(_, state)| state.as_str() == Some("Unallocated"))
+                .map(|(item_id, _)| {
+                    json!({ "V_1": [{
+                        "destination": args["items"][item_id]["pickup_id"],
+                        "work": { "load_items": [item_id], "unload_items": [] },
+                    }] })
+                })
+                .unwrap_or_else(|| json!({}));
+            let response = plan.to_string();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+                response.len()
+            )
+            .unwrap();
+            requests += 1;
+        }
+        requests
+    });
+
+    let recorder = Recorder::default();
+    let mut sim = testing::simulator()
+        .scheduler(Box::new(RemoteScheduler::new(url).unwrap()))
+        .callback(Box::new(recorder.clone()))
+        .build()
+        .unwrap();
+    // dispatches at 00:00 and 01:40
+    sim.simulate_until(sim.start_time() + Duration::hours(2))
+        .unwrap();
+    assert_eq!(server.join().unwrap(), 2);
+    let dispatches = recorder.dispatches();
+    assert!(dispatches[0].output.is_empty());
+    let route = &dispatches[1].output[&VehicleId("V_1".to_string())][0];
+    assert_eq!(route.work.load_items.len(), 1);
+}
+
+#[test]
+fn test_remote_scheduler_failure_stops_simulation() {
+    use std::net::TcpListener;
+
+    use crate::{simulation::error::SimulationError, testing};
+
+    // nothing listens on the port once the listener is dropped
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let mut sim = testing::simulator()
+        .scheduler(Box::new(
+            RemoteScheduler::new(format!("http://{addr}/dispatch")).unwrap(),
+        ))
+        .build()
+        .unwrap();
+    let err = sim.simulate_step().unwrap_err();
+    assert!(matches!(err, SimulationError::Scheduler { .. }), "{err}");
+}
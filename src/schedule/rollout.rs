@@ -0,0 +1,195 @@
+use chrono::Duration;
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{
+    model::{order::Order, vehicle_info::VehicleId, MapType},
+    simulation::{result::SimulationResult, simulator::VehicleRoute},
+};
+
+use super::{insertion::InsertionScheduler, planning::PlanningContext, Scheduler, SchedulerArgs};
+
+/// Parameters of [`RolloutScheduler`].
+#[derive(Debug, Clone)]
+pub struct RolloutConfig {
+    /// Number of candidate plans, including the one of the base policy.
+    pub num_candidates: usize,
+    /// How far ahead of the dispatch each candidate is simulated. Orders
+    /// still undelivered by then count as delivered at the end.
+    pub horizon: Duration,
+    pub seed: u64,
+}
+
+impl Default for RolloutConfig {
+    fn default() -> Self {
+        Self {
+            num_candidates: 8,
+            horizon: Duration::days(1),
+            seed: 0,
+        }
+    }
+}
+
+/// Picks the best of several candidate plans by simulating each of them.
+///
+/// The candidates are the plan of the base policy and cheapest insertions of
+/// the unallocated orders in random order. Every candidate is applied to a
+/// fork of `static_simulator`, which only knows the orders that have arrived,
+/// and simulated forward with the base policy dispatching at the usual
+/// interval until all of them are delivered or the horizon is reached. The
+/// candidate with the best objective is returned.
+pub struct RolloutScheduler<S> {
+    base: S,
+    config: RolloutConfig,
+    rng: SmallRng,
+}
+
+impl<S: Scheduler + Clone + 'static> RolloutScheduler<S> {
+    pub fn new(base: S, config: RolloutConfig) -> Self {
+        let rng = SmallRng::seed_from_u64(config.seed);
+        Self { base, config, rng }
+    }
+}
+
+pub(crate) type Plan = MapType<VehicleId, Vec<VehicleRoute>>;
+
+/// The base plan followed by up to `num_candidates - 1` cheapest insertions
+/// of the unallocated orders in random order. Only the base plan if there is
+/// nothing to allocate.
+pub(crate) fn candidate_plans<R: Rng>(
+    args: &SchedulerArgs,
+    base_plan: Plan,
+    num_candidates: usize,
+    rng: &mut R,
+) -> Vec<Plan> {
+    let ctx = PlanningContext::new(args);
+    let units = ctx.unallocated_units();
+    let mut candidates = vec![base_plan];
+    if units.is_empty() {
+        return candidates;
+    }
+    for _ in 1..num_candidates {
+        let mut units = units.clone();
+        units.shuffle(rng);
+        let routes = InsertionScheduler::construct_in_order(&ctx, units);
+        candidates.push(ctx.to_plan(&routes));
+    }
+    candidates
+}
+
+/// Objective of following the plan from a fork of `static_simulator` in
+/// which `future_orders` arrive, with `base` dispatching at the usual
+/// interval until the horizon. Orders still undelivered by then count as
+/// delivered at the end. `None` if the plan is infeasible.
+pub(crate) fn simulate_plan<S: Scheduler + Clone + 'static>(
+    args: &SchedulerArgs,
+    base: &S,
+    plan: &Plan,
+    horizon: Duration,
+    future_orders: impl IntoIterator<Item = Order>,
+) -> Option<f64> {
+    let mut sim = args.static_simulator.rollout_fork(Box::new(base.clone()));
+    let config = sim.config().clone();
+    sim.inject_orders(future_orders).ok()?;
+    sim.apply_plan(plan.clone(), args.time).ok()?;
+    sim.request_dispatch(args.time + config.time_interval);
+    let end = args.time + horizon;
+    sim.simulate_until(end).ok()?;
+
+    let SimulationResult {
+        mut orders,
+        vehicles,
+        ..
+    } = sim.result();
+    for order in orders.iter_mut().filter(|o| o.deliver_time.is_none()) {
+        order.deliver_time = Some(end);
+        order.lateness = (end - order.deadline).max(Duration::zero());
+    }
+    let horizon = end - sim.start_time();
+    Some(SimulationResult::new(orders, vehicles, horizon, config.objective).objective)
+}
+
+impl<S: Scheduler + Clone + 'static> Scheduler for RolloutScheduler<S> {
+    fn schedule(&mut self, args: SchedulerArgs) -> Plan {
+        let base_plan = self.base.schedule(args.clone());
+        let mut candidates =
+            candidate_plans(&args, base_plan, self.config.num_candidates, &mut self.rng);
+        if candidates.len() == 1 {
+            return candidates.remove(0);
+        }
+
+        let mut best: Option<(f64, Plan)> = None;
+        for candidate in candidates {
+            let rollout = simulate_plan(&args, &self.base, &candidate, self.config.horizon, []);
+            let Some(score) = rollout else {
+                continue;
+            };
+            if best.as_ref().is_none_or(|(best, _)| score < *best) {
+                best = Some((score, candidate));
+            }
+        }
+        // an infeasible base plan is rejected by the simulator as usual
+        best.map(|(_, plan)| plan)
+            .unwrap_or_else(|| self.base.schedule(args))
+    }
+}
+
+#[test]
+fn test_rollout_scheduler_picks_lowest_cost() {
+    use crate::{
+        model::Map as _,
+        simulation::simulator::OrderItemState,
+        testing::{self, Recorder},
+    };
+
+    let recorder = Recorder::default();
+    let mut sim = testing::simulator()
+        .scheduler(Box::new(InsertionScheduler))
+        .callback(Box::new(recorder.clone()))
+        .build()
+        .unwrap();
+    sim.simulate_until(sim.start_time() + Duration::hours(4))
+        .unwrap();
+    // the first dispatch with several orders to allocate
+    let dispatch = recorder
+        .dispatches()
+        .iter()
+        .find(|dispatch| {
+            let unallocated = dispatch
+                .args
+                .item_states
+                .values()
+                .filter(|state| **state == OrderItemState::Unallocated)
+                .count();
+            unallocated > 2
+        })
+        .unwrap()
+        .clone();
+    let args = || dispatch.parse();
+
+    let config = RolloutConfig {
+        num_candidates: 4,
+        seed: 727,
+        ..Default::default()
+    };
+    let base_plan = InsertionScheduler.schedule(args());
+    let mut rng = SmallRng::seed_from_u64(config.seed);
+    let candidates = candidate_plans(&args(), base_plan, config.num_candidates, &mut rng);
+    let scores: Vec<f64> = candidates
+        .iter()
+        .map(|plan| simulate_plan(&args(), &InsertionScheduler, plan, config.horizon, []).unwrap())
+        .collect();
+    assert_eq!(candidates.len(), config.num_candidates);
+    let lowest = (0..scores.len())
+        .min_by(|a, b| scores[*a].total_cmp(&scores[*b]))
+        .unwrap();
+    // the plan of the base policy is beaten in this snapshot
+    assert_ne!(lowest, 0);
+
+    for _ in 0..2 {
+        let plan = RolloutScheduler::new(InsertionScheduler, config.clone()).schedule(args());
+        assert_eq!(
+            serde_json::to_string(&plan).unwrap(),
+            serde_json::to_string(&candidates[lowest]).unwrap()
+        );
+    }
+}
@@ -0,0 +1,386 @@
+use std::{
+    fs::{create_dir_all, File},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{anyhow, Context as _};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    model::{
+        factory_info::FactoryId,
+        order::OrderId,
+        order_item::{OrderItem, OrderItemId, OrderItemType},
+        vehicle_info::VehicleId,
+        Map as _, MapType,
+    },
+    simulation::{
+        sim_event::VehicleWork,
+        simulator::{OrderItemState, VehiclePosition, VehicleRoute},
+    },
+};
+
+use super::{Scheduler, SchedulerArgs};
+
+/// Directory below the working directory of the competition simulator in
+/// which it exchanges files with the solution.
+pub const DATA_INTERACTION_DIR: &str = "algorithm/data_interaction";
+
+/// Runs a solution of the original DPDP competition as a subprocess.
+///
+/// The command runs in `root`, like the competition simulator runs the
+/// solution in its project directory. Before every dispatch
+/// `vehicle_info.json`, `unallocated_order_items.json` and
+/// `ongoing_order_items.json` are written to
+/// `root/algorithm/data_interaction` in the format of the competition
+/// simulator. After the command exits successfully,
+/// `output_destination.json` and `output_route.json` are read back from the
+/// same directory. Relative paths in the command are resolved against
+/// `root`.
+///
+/// Times are Unix timestamps of the naive simulation time, demands and
+/// capacities are in standard pallets.
+pub struct SubprocessScheduler {
+    command: Vec<String>,
+    root: PathBuf,
+}
+
+impl SubprocessScheduler {
+    /// `command` is the program followed by its arguments.
+    pub fn new(command: Vec<String>, root: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        if command.is_empty() {
+            return Err(anyhow!("empty subprocess scheduler command"));
+        }
+        Ok(Self {
+            command,
+            root: root.into(),
+        })
+    }
+
+    fn data_dir(&self) -> PathBuf {
+        self.root.join(DATA_INTERACTION_DIR)
+    }
+}
+
+impl Scheduler for SubprocessScheduler {
+    /// Falls back to an empty plan if the command fails, so every vehicle
+    /// keeps following its previous route. Only used outside the simulator,
+    /// which stops with an error instead.
+    fn schedule(&mut self, args: SchedulerArgs) -> MapType<VehicleId, Vec<VehicleRoute>> {
+        self.try_schedule(args).unwrap_or_else(|err| {
+            tracing::warn!("subprocess scheduler {:?} failed: {err:#}", self.command);
+            MapType::new()
+        })
+    }
+
+    fn try_schedule(
+        &mut self,
+        args: SchedulerArgs,
+    ) -> anyhow::Result<MapType<VehicleId, Vec<VehicleRoute>>> {
+        let data_dir = self.data_dir();
+        write_input(&data_dir, &args)?;
+
+        let status = Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .current_dir(&self.root)
+            .status()
+            .with_context(|| format!("unable to run {:?}", self.command))?;
+        if !status.success() {
+            return Err(anyhow!("{:?} exited with {status}", self.command));
+        }
+
+        read_output(&data_dir, &args)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompetitionNode {
+    pub factory_id: FactoryId,
+    #[serde(default)]
+    pub lng: f64,
+    #[serde(default)]
+    pub lat: f64,
+    /// Unloaded in this order, so the first item must be on top of the stack.
+    pub delivery_item_list: Vec<OrderItemId>,
+    pub pickup_item_list: Vec<OrderItemId>,
+    #[serde(default)]
+    pub arrive_time: i64,
+    #[serde(default)]
+    pub leave_time: i64,
+}
+
+impl From<CompetitionNode> for VehicleRoute {
+    fn from(node: CompetitionNode) -> Self {
+        let mut unload_items = node.delivery_item_list;
+        unload_items.reverse();
+        // loading times are recomputed by the simulator
+        VehicleRoute::new(
+            node.factory_id,
+            VehicleWork {
+                load_items: node.pickup_item_list,
+                unload_items,
+                load_time: Default::default(),
+                unload_time: Default::default(),
+            },
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompetitionVehicle {
+    pub id: VehicleId,
+    pub operation_time: i32,
+    pub capacity: i32,
+    pub gps_id: String,
+    pub update_time: i64,
+    /// Empty while the vehicle is on the road.
+    pub cur_factory_id: String,
+    pub arrive_time_at_current_factory: i64,
+    pub leave_time_at_current_factory: i64,
+    /// Bottom of the stack first.
+    pub carrying_items: Vec<OrderItemId>,
+    pub destination: Option<CompetitionNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompetitionOrderItem {
+    pub id: OrderItemId,
+    #[serde(rename = "type")]
+    pub item_type: &'static str,
+    pub order_id: OrderId,
+    pub demand: f64,
+    pub pickup_factory_id: FactoryId,
+    pub delivery_factory_id: FactoryId,
+    pub creation_time: i64,
+    pub committed_completion_time: i64,
+    pub load_time: i64,
+    pub unload_time: i64,
+    pub delivery_state: i32,
+}
+
+impl CompetitionOrderItem {
+    fn new(item: &OrderItem, state: &OrderItemState, args: &SchedulerArgs) -> Self {
+        let date = args.static_simulator.initial_date();
+        Self {
+            id: item.id.clone(),
+            item_type: match item.id.item_type {
+                OrderItemType::Standard => "PALLET",
+                OrderItemType::Small => "HALF_PALLET",
+                OrderItemType::Box => "BOX",
+            },
+            order_id: item.id.order_id.clone(),
+            demand: item.demand as f64 / 4.0,
+            pickup_factory_id: item.pickup_id.clone(),
+            delivery_factory_id: item.delivery_id.clone(),
+            creation_time: timestamp(date.and_time(item.creation_time)),
+            committed_completion_time: timestamp(item.committed_completion_time(date)),
+            load_time: item.load_time.num_seconds(),
+            unload_time: item.unload_time.num_seconds(),
+            delivery_state: match state {
+                OrderItemState::Unavailable => 0,
+                OrderItemState::Unallocated => 1,
+                OrderItemState::Allocated | OrderItemState::PickedUp => 2,
+                OrderItemState::Delivered { .. } => 3,
+            },
+        }
+    }
+}
+
+fn timestamp(time: NaiveDateTime) -> i64 {
+    time.and_utc().timestamp()
+}
+
+fn dump(dir: &Path, filename: &str, value: &impl Serialize) -> anyhow::Result<()> {
+    let path = dir.join(filename);
+    let file =
+        File::create(&path).with_context(|| format!("unable to create {}", path.display()))?;
+    serde_json::to_writer_pretty(file, value)?;
+    Ok(())
+}
+
+fn load<T: for<'de> Deserialize<'de>>(dir: &Path, filename: &str) -> anyhow::Result<T> {
+    let path = dir.join(filename);
+    let file = File::open(&path).with_context(|| format!("unable to open {}", path.display()))?;
+    serde_json::from_reader(file).with_context(|| format!("invalid {}", path.display()))
+}
+
+pub fn write_input(dir: &Path, args: &SchedulerArgs) -> anyhow::Result<()> {
+    create_dir_all(dir)?;
+    let update_time = timestamp(args.time);
+
+    let vehicles: Vec<_> = args
+        .static_simulator
+        .vehicles()
+        .values()
+        .map(|info| {
+            let (cur_factory_id, destination) = match &args.vehicle_positions[&info.car_num] {
+                VehiclePosition::Idle(factory_id) | VehiclePosition::DoingWork(factory_id) => {
+                    (factory_id.to_string(), None)
+                }
+                VehiclePosition::Transporting(_, to) => {
+                    let sim = &args.static_simulator;
+                    let factory = sim.factories().get(to);
+                    let leg = sim.current_leg(&info.car_num);
+                    let work = leg.map(|(_, work)| work);
+                    let destination = CompetitionNode {
+                        factory_id: to.clone(),
+                        lng: factory.map(|f| f.longitude).unwrap_or_default(),
+                        lat: factory.map(|f| f.latitude).unwrap_or_default(),
+                        delivery_item_list: work
+                            .map(|w| w.unload_items.iter().rev().cloned().collect())
+                            .unwrap_or_default(),
+                        pickup_item_list: work.map(|w| w.load_items.clone()).unwrap_or_default(),
+                        arrive_time: leg.map_or(0, |(arrival, _)| timestamp(arrival)),
+                        leave_time: leg.map_or(0, |_| {
+                            timestamp(sim.estimate_ready_time(&info.car_num, args.time))
+                        }),
+                    };
+                    (String::new(), Some(destination))
+                }
+            };
+            CompetitionVehicle {
+                id: info.car_num.clone(),
+                operation_time: info.operation_time,
+                capacity: info.capacity() / 4,
+                gps_id: info.gps_id.clone(),
+                update_time,
+                cur_factory_id,
+                arrive_time_at_current_factory: update_time,
+                leave_time_at_current_factory: update_time,
+                carrying_items: args
+                    .vehicle_stacks
+                    .get(&info.car_num)
+                    .cloned()
+                    .unwrap_or_default(),
+                destination,
+            }
+        })
+        .collect();
+
+    let items_in_state = |pred: fn(&OrderItemState) -> bool| -> Vec<_> {
+        args.items
+            .values()
+            .filter_map(|item| {
+                let state = args.item_states.gets(&item.id);
+                pred(state).then(|| CompetitionOrderItem::new(item, state, args))
+            })
+            .collect()
+    };
+
+    dump(dir, "vehicle_info.json", &vehicles)?;
+    dump(
+        dir,
+        "unallocated_order_items.json",
+        &items_in_state(|s| *s == OrderItemState::Unallocated),
+    )?;
+    dump(
+        dir,
+        "ongoing_order_items.json",
+        &items_in_state(|s| matches!(s, OrderItemState::Allocated | OrderItemState::PickedUp)),
+    )?;
+    Ok(())
+}
+
+/// Converts the competition output into routes. The destination of a vehicle
+/// on the road is the leg it is already following, so it is dropped.
+pub fn read_output(
+    dir: &Path,
+    args: &SchedulerArgs,
+) -> anyhow::Result<MapType<VehicleId, Vec<VehicleRoute>>> {
+    let mut destinations: MapType<VehicleId, Option<CompetitionNode>> =
+        load(dir, "output_destination.json")?;
+    let mut routes: MapType<VehicleId, Vec<CompetitionNode>> = load(dir, "output_route.json")?;
+
+    let vehicle_ids: Vec<_> = destinations.keys().chain(routes.keys()).cloned().collect();
+    let mut plan = MapType::new();
+    for vehicle_id in vehicle_ids {
+        if plan.contains_key(&vehicle_id) {
+            continue;
+        }
+        let on_road = matches!(
+            args.vehicle_positions.get(&vehicle_id),
+            Some(VehiclePosition::Transporting(..))
+        );
+        let destination = destinations
+            .remove(&vehicle_id)
+            .flatten()
+            .filter(|_| !on_road);
+        let route = routes.remove(&vehicle_id).unwrap_or_default();
+        let vehicle_plan: Vec<VehicleRoute> = destination
+            .into_iter()
+            .chain(route)
+            .map(VehicleRoute::from)
+            .collect();
+        plan.insert(vehicle_id, vehicle_plan);
+    }
+    Ok(plan)
+}
+
+#[test]
+fn test_subprocess_scheduler_exchange_files() {
+    use crate::{simulation::config::SimulatorConfig, testing};
+
+    let dir = std::env::temp_dir().join(format!("dpdp_subprocess_{}", std::process::id()));
+    // hand the first unallocated item to an empty V_1 and deliver it right
+    // away, log the vehicles on the road
+    let script = r#"
+        import json
+        dir = "algorithm/data_interaction/"
+        items = json.load(open(dir + "unallocated_order_items.json"))
+        vehicles = json.load(open(dir + "vehicle_info.json"))
+        with open(dir + "on_road.jsonl", "a") as log:
+            for vehicle in vehicles:
+                if vehicle["destination"]:
+                    log.write(json.dumps(vehicle) + "\n")
+        v1 = next(v for v in vehicles if v["id"] == "V_1")
+        destination, route = {}, {}
+        if items and not v1["carrying_items"] and not v1["destination"]:
+            item = items[0]
+            destination["V_1"] = {"factory_id": item["pickup_factory_id"],
+                                  "pickup_item_list": [item["id"]], "delivery_item_list": []}
+            route["V_1"] = [{"factory_id": item["delivery_factory_id"],
+                             "pickup_item_list": [], "delivery_item_list": [item["id"]]}]
+        json.dump(destination, open(dir + "output_destination.json", "w"))
+        json.dump(route, open(dir + "output_route.json", "w"))
+    "#;
+    let script = script
+        .lines()
+        .map(|l| l.strip_prefix("        ").unwrap_or(l))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let scheduler =
+        SubprocessScheduler::new(vec!["python3".into(), "-c".into(), script], &dir).unwrap();
+
+    let mut sim = testing::simulator()
+        .config(SimulatorConfig {
+            // dispatch while V_1 is on the road
+            time_interval: chrono::Duration::minutes(10),
+            ..Default::default()
+        })
+        .scheduler(Box::new(scheduler))
+        .build()
+        .unwrap();
+    sim.simulate_until(sim.start_time() + chrono::Duration::hours(3))
+        .unwrap();
+    let data_dir = dir.join(DATA_INTERACTION_DIR);
+    assert!(data_dir.join("vehicle_info.json").exists());
+    assert!(data_dir.join("ongoing_order_items.json").exists());
+    let result = sim.result();
+    assert!(result.orders.iter().any(|o| o.deliver_time.is_some()));
+
+    // vehicles on the road report the leg they are driving
+    let on_road = std::fs::read_to_string(data_dir.join("on_road.jsonl")).unwrap();
+    assert!(!on_road.is_empty());
+    for line in on_road.lines() {
+        let vehicle: serde_json::Value = serde_json::from_str(line).unwrap();
+        let destination = &vehicle["destination"];
+        assert!(destination["arrive_time"].as_i64() >= vehicle["update_time"].as_i64());
+        assert!(destination["leave_time"].as_i64() >= destination["arrive_time"].as_i64());
+        let items = ["pickup_item_list", "delivery_item_list"]
+            .map(|list| destination[list].as_array().unwrap().len());
+        assert!(items.iter().sum::<usize>() > 0);
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -4,12 +4,36 @@ use dyn_clone::DynClone;
 
 use crate::{model::vehicle_info::VehicleId, schedule::SchedulerArgs};
 
-use super::simulator::{SimEvent, VehicleRoute};
+use super::{
+    error::SimulationError,
+    simulator::{SimEvent, VehicleRoute},
+};
 
+/// Observes a simulation. An error returned by any hook aborts the run with
+/// [`SimulationError::Callback`].
 pub trait SimulationCallback: DynClone {
-    fn visit_event(&mut self, event: &SimEvent) {}
-    fn visit_dispatch_input(&mut self, input: &SchedulerArgs) {}
-    fn visit_dispatch_output(&mut self, output: &BTreeMap<VehicleId, Vec<VehicleRoute>>) {}
+    fn visit_event(&mut self, _event: &SimEvent) -> anyhow::Result<()> {
+        Ok(())
+    }
+    fn visit_dispatch_input(&mut self, _input: &SchedulerArgs) -> anyhow::Result<()> {
+        Ok(())
+    }
+    fn visit_dispatch_output(
+        &mut self,
+        _output: &BTreeMap<VehicleId, Vec<VehicleRoute>>,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+    /// Called for every vehicle plan rejected by the simulator, unless the
+    /// run is aborted by
+    /// [`InfeasiblePlanPolicy::Abort`](super::config::InfeasiblePlanPolicy::Abort).
+    fn visit_rejected_plan(
+        &mut self,
+        _routes: &[VehicleRoute],
+        _error: &SimulationError,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
 
 dyn_clone::clone_trait_object!(SimulationCallback);
@@ -0,0 +1,156 @@
+use std::path::Path;
+
+use anyhow::Context as _;
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use super::objective::Objective;
+
+/// Physical constants and timing parameters of a simulation run.
+///
+/// Durations are (de)serialized as human-readable strings such as `"10m"` or
+/// `"1h 30m"`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimulatorConfig {
+    /// Interval between two consecutive dispatches.
+    #[serde(with = "humantime_duration")]
+    pub time_interval: Duration,
+    /// Time needed for a vehicle to approach a dock after arriving at a
+    /// factory.
+    #[serde(with = "humantime_duration")]
+    pub dock_approaching_time: Duration,
+    /// Loading time per box (1/4 standard pallet).
+    #[serde(with = "humantime_duration")]
+    pub load_time_per_box: Duration,
+    /// Unloading time per box (1/4 standard pallet).
+    #[serde(with = "humantime_duration")]
+    pub unload_time_per_box: Duration,
+    /// Date of the simulated day. All timestamps are naive and relative to
+    /// this date, so runs do not depend on the wall clock or the local
+    /// timezone. Defaults to 1970-01-01.
+    pub initial_date: NaiveDate,
+    /// Whether the wall-clock time spent by the scheduler delays the next
+    /// dispatch by whole intervals, like in the competition. Off by default,
+    /// since the outcome then depends on the machine.
+    pub charge_scheduling_time: bool,
+    /// Objective reported as the score of a run.
+    pub objective: Objective,
+    /// What to do when the scheduler returns an infeasible plan.
+    pub infeasible_plan_policy: InfeasiblePlanPolicy,
+    /// Whether all orders of the instance are known to the scheduler from
+    /// the first dispatch on. Items are still not loaded before their
+    /// creation time, the vehicle waits at the dock for them.
+    pub clairvoyant: bool,
+    /// Simulated time after the start of the day at which the run stops with
+    /// [`SimulationError::Unfinished`](super::error::SimulationError::Unfinished),
+    /// in case the scheduler never delivers some orders.
+    #[serde(with = "humantime_duration")]
+    pub max_duration: Duration,
+}
+
+/// How the simulator reacts to plans that violate the problem constraints.
+/// Every rejected vehicle plan is reported through
+/// [`SimulationCallback::visit_rejected_plan`](super::callback::SimulationCallback::visit_rejected_plan).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InfeasiblePlanPolicy {
+    /// Stop the simulation with a [`SimulationError`](super::error::SimulationError).
+    #[default]
+    Abort,
+    /// Discard the whole new plan, every vehicle keeps following its previous
+    /// route.
+    KeepPrevious,
+    /// Apply the plans of the other vehicles. Offending vehicles keep
+    /// following their previous route, and plans loading items that route
+    /// still loads are dropped as well.
+    DropVehicle,
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        Self {
+            time_interval: Duration::minutes(100),
+            dock_approaching_time: Duration::minutes(30),
+            load_time_per_box: Duration::minutes(1),
+            unload_time_per_box: Duration::minutes(1),
+            initial_date: NaiveDate::default(),
+            charge_scheduling_time: false,
+            objective: Objective::default(),
+            infeasible_plan_policy: InfeasiblePlanPolicy::default(),
+            clairvoyant: false,
+            max_duration: Duration::days(7),
+        }
+    }
+}
+
+impl SimulatorConfig {
+    /// Settings of the original DPDP competition simulator, which dispatches
+    /// every 10 minutes and charges the scheduling time.
+    pub fn competition() -> Self {
+        Self {
+            time_interval: Duration::minutes(10),
+            charge_scheduling_time: true,
+            ..Default::default()
+        }
+    }
+
+    /// Loads a config from a TOML file, or from a JSON file if the extension
+    /// is not `.toml`.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("unable to read config file {}", path.display()))?;
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            Ok(toml::from_str(&content)?)
+        } else {
+            Ok(serde_json::from_str(&content)?)
+        }
+    }
+}
+
+mod humantime_duration {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let duration = duration.to_std().map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&humantime::format_duration(duration).to_string())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let duration = humantime::parse_duration(&s).map_err(serde::de::Error::custom)?;
+        Duration::from_std(duration).map_err(serde::de::Error::custom)
+    }
+}
+
+#[test]
+fn test_config_roundtrip() {
+    let config = SimulatorConfig {
+        initial_date: NaiveDate::from_ymd_opt(2021, 6, 1).unwrap(),
+        ..SimulatorConfig::competition()
+    };
+    let toml_str = toml::to_string(&config).unwrap();
+    assert_eq!(
+        toml::from_str::<SimulatorConfig>(&toml_str).unwrap(),
+        config
+    );
+    let objective: SimulatorConfig =
+        toml::from_str("[objective]\ntype = \"competition\"\ntimeout_weight = 1.0").unwrap();
+    assert_eq!(
+        objective.objective,
+        Objective::Competition {
+            timeout_weight: 1.0
+        }
+    );
+    let partial: SimulatorConfig =
+        toml::from_str("time_interval = \"10m\"\ncharge_scheduling_time = true").unwrap();
+    assert_eq!(partial, SimulatorConfig::competition());
+}
@@ -0,0 +1,252 @@
+use std::fmt::Display;
+
+use chrono::NaiveDateTime;
+
+use crate::model::{
+    factory_info::FactoryId, order::OrderId, order_item::OrderItemId, vehicle_info::VehicleId,
+};
+
+use super::simulator::{OrderItemState, VehiclePosition};
+
+/// A violation of the problem constraints, usually caused by an infeasible
+/// plan returned by the scheduler, or a failed [`SimulationCallback`].
+///
+/// [`SimulationCallback`]: super::callback::SimulationCallback
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimulationError {
+    CapacityViolation {
+        vehicle_id: VehicleId,
+        demand: i32,
+        capacity: i32,
+        time: NaiveDateTime,
+    },
+    LifoViolation {
+        vehicle_id: VehicleId,
+        item_id: OrderItemId,
+        // item on top of the stack at that moment
+        top: Option<OrderItemId>,
+        time: NaiveDateTime,
+    },
+    WrongPickupFactory {
+        vehicle_id: VehicleId,
+        item_id: OrderItemId,
+        expected: FactoryId,
+        actual: FactoryId,
+        time: NaiveDateTime,
+    },
+    WrongDeliveryFactory {
+        vehicle_id: VehicleId,
+        item_id: OrderItemId,
+        expected: FactoryId,
+        actual: FactoryId,
+        time: NaiveDateTime,
+    },
+    IllegalSplit {
+        vehicle_id: VehicleId,
+        order_id: OrderId,
+        demand: i32,
+        capacity: i32,
+        time: NaiveDateTime,
+    },
+    InvalidItemState {
+        vehicle_id: VehicleId,
+        item_id: OrderItemId,
+        state: OrderItemState,
+        time: NaiveDateTime,
+    },
+    UnknownVehicle {
+        vehicle_id: VehicleId,
+        time: NaiveDateTime,
+    },
+    UnknownOrder {
+        vehicle_id: VehicleId,
+        order_id: OrderId,
+        time: NaiveDateTime,
+    },
+    UnknownItem {
+        vehicle_id: VehicleId,
+        item_id: OrderItemId,
+        time: NaiveDateTime,
+    },
+    /// The item is also loaded by the route of vehicle `owner`.
+    ItemConflict {
+        vehicle_id: VehicleId,
+        item_id: OrderItemId,
+        owner: VehicleId,
+        time: NaiveDateTime,
+    },
+    /// An event at `factory_id` found the vehicle elsewhere.
+    UnexpectedPosition {
+        vehicle_id: VehicleId,
+        factory_id: FactoryId,
+        position: VehiclePosition,
+        time: NaiveDateTime,
+    },
+    Callback {
+        message: String,
+        time: NaiveDateTime,
+    },
+    /// [`Scheduler::try_schedule`](crate::schedule::Scheduler::try_schedule)
+    /// failed.
+    Scheduler {
+        message: String,
+        time: NaiveDateTime,
+    },
+    /// Orders are still not delivered after
+    /// [`SimulatorConfig::max_duration`](super::config::SimulatorConfig::max_duration).
+    Unfinished {
+        undelivered: usize,
+        time: NaiveDateTime,
+    },
+}
+
+impl SimulationError {
+    pub fn vehicle_id(&self) -> Option<&VehicleId> {
+        Some(match self {
+            Self::CapacityViolation { vehicle_id, .. }
+            | Self::LifoViolation { vehicle_id, .. }
+            | Self::WrongPickupFactory { vehicle_id, .. }
+            | Self::WrongDeliveryFactory { vehicle_id, .. }
+            | Self::IllegalSplit { vehicle_id, .. }
+            | Self::InvalidItemState { vehicle_id, .. }
+            | Self::UnknownVehicle { vehicle_id, .. }
+            | Self::UnknownOrder { vehicle_id, .. }
+            | Self::UnknownItem { vehicle_id, .. }
+            | Self::ItemConflict { vehicle_id, .. }
+            | Self::UnexpectedPosition { vehicle_id, .. } => vehicle_id,
+            Self::Callback { .. } | Self::Scheduler { .. } | Self::Unfinished { .. } => {
+                return None
+            }
+        })
+    }
+
+    pub fn time(&self) -> NaiveDateTime {
+        match self {
+            Self::CapacityViolation { time, .. }
+            | Self::LifoViolation { time, .. }
+            | Self::WrongPickupFactory { time, .. }
+            | Self::WrongDeliveryFactory { time, .. }
+            | Self::IllegalSplit { time, .. }
+            | Self::InvalidItemState { time, .. }
+            | Self::UnknownVehicle { time, .. }
+            | Self::UnknownOrder { time, .. }
+            | Self::UnknownItem { time, .. }
+            | Self::ItemConflict { time, .. }
+            | Self::UnexpectedPosition { time, .. }
+            | Self::Callback { time, .. }
+            | Self::Scheduler { time, .. }
+            | Self::Unfinished { time, .. } => *time,
+        }
+    }
+}
+
+impl Display for SimulationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CapacityViolation {
+                vehicle_id,
+                demand,
+                capacity,
+                time,
+            } => write!(
+                f,
+                "Violate capacity constraint on vehicle {vehicle_id} at {time}: demand {demand} > capacity {capacity}"
+            ),
+            Self::LifoViolation {
+                vehicle_id,
+                item_id,
+                top,
+                time,
+            } => write!(
+                f,
+                "Violate LIFO constraint on vehicle {vehicle_id} at {time}: unloading {item_id}, but the top of the stack is {top:?}"
+            ),
+            Self::WrongPickupFactory {
+                vehicle_id,
+                item_id,
+                expected,
+                actual,
+                time,
+            } => write!(
+                f,
+                "Order item {item_id} pickup location is {expected}, not {actual} (vehicle {vehicle_id} at {time})"
+            ),
+            Self::WrongDeliveryFactory {
+                vehicle_id,
+                item_id,
+                expected,
+                actual,
+                time,
+            } => write!(
+                f,
+                "Order item {item_id} delivery location is {expected}, not {actual} (vehicle {vehicle_id} at {time})"
+            ),
+            Self::IllegalSplit {
+                vehicle_id,
+                order_id,
+                demand,
+                capacity,
+                time,
+            } => write!(
+                f,
+                "Order {order_id} has demand {demand} <= capacity {capacity} is split by vehicle {vehicle_id} at {time} (orders can only be split if the demand exceeds vehicle capacity)"
+            ),
+            Self::InvalidItemState {
+                vehicle_id,
+                item_id,
+                state,
+                time,
+            } => write!(
+                f,
+                "Order item {item_id} cannot be handled by vehicle {vehicle_id} at {time} in state {state:?}"
+            ),
+            Self::UnknownVehicle { vehicle_id, time } => {
+                write!(f, "Invalid vehicle ID: {vehicle_id} (at {time})")
+            }
+            Self::UnknownOrder {
+                vehicle_id,
+                order_id,
+                time,
+            } => write!(
+                f,
+                "Invalid order ID: {order_id} (vehicle {vehicle_id} at {time})"
+            ),
+            Self::UnknownItem {
+                vehicle_id,
+                item_id,
+                time,
+            } => write!(
+                f,
+                "Invalid order item ID: {item_id} (vehicle {vehicle_id} at {time})"
+            ),
+            Self::ItemConflict {
+                vehicle_id,
+                item_id,
+                owner,
+                time,
+            } => write!(
+                f,
+                "Order item {item_id} loaded by vehicle {vehicle_id} at {time} is also loaded by vehicle {owner}"
+            ),
+            Self::UnexpectedPosition {
+                vehicle_id,
+                factory_id,
+                position,
+                time,
+            } => write!(
+                f,
+                "Vehicle {vehicle_id} is expected at factory {factory_id} at {time}, but its position is {position:?}"
+            ),
+            Self::Callback { message, time } => write!(f, "Callback failed at {time}: {message}"),
+            Self::Scheduler { message, time } => {
+                write!(f, "Scheduler failed at {time}: {message}")
+            }
+            Self::Unfinished { undelivered, time } => write!(
+                f,
+                "{undelivered} orders are still not delivered at {time}, giving up"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
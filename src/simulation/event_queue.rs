@@ -1,59 +1,108 @@
-use std::{cmp::Reverse, collections::BinaryHeap};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+};
 
 use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
 
 pub trait Event {
     fn time(&self) -> NaiveDateTime;
 
+    /// Events at the same time are popped in ascending priority, and events
+    /// with equal priority in insertion order.
+    fn priority(&self) -> u8 {
+        0
+    }
+
     fn time_rev(&self) -> Reverse<NaiveDateTime> {
         Reverse(self.time())
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct EventWrapper<E: Event>(E);
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventWrapper<E: Event> {
+    seq: u64,
+    event: E,
+}
+
+impl<E: Event> EventWrapper<E> {
+    // BinaryHeap is a max-heap, so every component is reversed
+    fn key(&self) -> (Reverse<NaiveDateTime>, Reverse<u8>, Reverse<u64>) {
+        (
+            self.event.time_rev(),
+            Reverse(self.event.priority()),
+            Reverse(self.seq),
+        )
+    }
+}
 
 impl<E: Event> PartialEq for EventWrapper<E> {
     fn eq(&self, other: &Self) -> bool {
-        self.0.time_rev() == other.0.time_rev()
+        self.key() == other.key()
     }
 }
 
 impl<E: Event> PartialOrd for EventWrapper<E> {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
 impl<E: Event> Eq for EventWrapper<E> {}
 impl<E: Event> Ord for EventWrapper<E> {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.time_rev().cmp(&other.0.time_rev())
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
     }
 }
 
-#[derive(Debug, Clone)]
+/// Min-queue of events ordered by time, then [`Event::priority`], then
+/// insertion order. Serialized with the insertion sequence numbers, so a
+/// restored queue pops events in the same order as the original.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventQueue<E: Event> {
     events: BinaryHeap<EventWrapper<E>>,
+    next_seq: u64,
 }
 
 impl<E: Event> EventQueue<E> {
     pub fn new() -> EventQueue<E> {
         EventQueue {
             events: BinaryHeap::new(),
+            next_seq: 0,
         }
     }
 
     pub fn push(&mut self, event: E) {
-        self.events.push(EventWrapper(event));
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.events.push(EventWrapper { seq, event });
     }
 
     pub fn pop(&mut self) -> Option<E> {
-        self.events.pop().map(|EventWrapper(e)| e)
+        self.events.pop().map(|wrapper| wrapper.event)
     }
 
     pub fn peek(&self) -> Option<&E> {
-        self.events.peek().map(|EventWrapper(e)| e)
+        self.events.peek().map(|wrapper| &wrapper.event)
+    }
+
+    /// Iterates over the pending events in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        self.events.iter().map(|wrapper| &wrapper.event)
+    }
+
+    /// Keeps only the events for which `keep` returns true.
+    pub fn retain(&mut self, mut keep: impl FnMut(&E) -> bool) {
+        self.events.retain(|wrapper| keep(&wrapper.event));
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
     }
 }
 
@@ -62,3 +111,44 @@ impl<E: Event> Default for EventQueue<E> {
         Self::new()
     }
 }
+
+#[cfg(test)]
+impl Event for (&'static str, u8, NaiveDateTime) {
+    fn time(&self) -> NaiveDateTime {
+        self.2
+    }
+
+    fn priority(&self) -> u8 {
+        self.1
+    }
+}
+
+#[test]
+fn test_event_queue_tie_breaking() {
+    let t0 = NaiveDateTime::default();
+    let t1 = t0 + chrono::Duration::minutes(1);
+    let mut queue = EventQueue::new();
+    for event in [
+        ("late", 0, t1),
+        ("first", 1, t0),
+        ("low priority", 2, t0),
+        ("second", 1, t0),
+        ("high priority", 0, t0),
+        ("third", 1, t0),
+    ] {
+        queue.push(event);
+    }
+
+    let order: Vec<_> = std::iter::from_fn(|| queue.pop()).map(|e| e.0).collect();
+    assert_eq!(
+        order,
+        [
+            "high priority",
+            "first",
+            "second",
+            "third",
+            "low priority",
+            "late"
+        ]
+    );
+}
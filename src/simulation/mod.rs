@@ -1,4 +1,10 @@
 pub mod callback;
+pub mod config;
+pub mod error;
 pub mod event_queue;
+pub mod objective;
+pub mod replay;
+pub mod result;
+pub mod scenario;
 pub mod sim_event;
 pub mod simulator;
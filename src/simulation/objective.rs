@@ -0,0 +1,88 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use super::result::SimulationResult;
+
+/// Weight of the total timeout (in hours) in the official ICAPS 2021 DPDP
+/// score.
+pub const COMPETITION_TIMEOUT_WEIGHT: f64 = 10000.0;
+
+/// A scalar score of a simulation run, lower is better.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Objective {
+    /// `timeout_weight * total_timeout_hours + average_distance_per_vehicle`.
+    Competition { timeout_weight: f64 },
+    /// Total timeout in hours.
+    TotalTimeout,
+    /// Total distance travelled by all vehicles in km.
+    TotalDistance,
+    /// Hours between the start of the simulation and the last delivery.
+    Makespan,
+    /// Number of orders delivered after their committed completion time.
+    LateOrders,
+    /// Number of vehicles that travelled at all.
+    VehiclesUsed,
+}
+
+impl Default for Objective {
+    fn default() -> Self {
+        Self::Competition {
+            timeout_weight: COMPETITION_TIMEOUT_WEIGHT,
+        }
+    }
+}
+
+impl Objective {
+    /// The official score and every alternative objective.
+    pub fn all() -> [Objective; 6] {
+        [
+            Self::default(),
+            Self::TotalTimeout,
+            Self::TotalDistance,
+            Self::Makespan,
+            Self::LateOrders,
+            Self::VehiclesUsed,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Competition { .. } => "competition",
+            Self::TotalTimeout => "total_timeout",
+            Self::TotalDistance => "total_distance",
+            Self::Makespan => "makespan",
+            Self::LateOrders => "late_orders",
+            Self::VehiclesUsed => "vehicles_used",
+        }
+    }
+
+    pub fn evaluate(&self, result: &SimulationResult) -> f64 {
+        match self {
+            Self::Competition { timeout_weight } => {
+                timeout_weight * result.total_timeout_hours() + result.average_distance() as f64
+            }
+            Self::TotalTimeout => result.total_timeout_hours(),
+            Self::TotalDistance => result.total_distance as f64,
+            Self::Makespan => result.horizon.num_seconds() as f64 / 3600.0,
+            Self::LateOrders => result
+                .orders
+                .iter()
+                .filter(|o| o.lateness > chrono::Duration::zero())
+                .count() as f64,
+            Self::VehiclesUsed => {
+                result.vehicles.iter().filter(|v| v.distance > 0.0).count() as f64
+            }
+        }
+    }
+}
+
+impl Display for Objective {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Competition { timeout_weight } => write!(f, "competition(λ={timeout_weight})"),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
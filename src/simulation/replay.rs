@@ -0,0 +1,163 @@
+use std::{cell::RefCell, collections::VecDeque, path::Path, rc::Rc};
+
+use anyhow::{anyhow, bail};
+use chrono::{Duration, NaiveDateTime};
+
+use crate::{
+    callbacks::trace::{read_trace, TraceEntry},
+    model::{vehicle_info::VehicleId, MapType},
+    schedule::{Scheduler, SchedulerArgs},
+};
+
+use super::{
+    callback::SimulationCallback,
+    sim_event::SimulatorEventData,
+    simulator::{Checkpoint, SimEvent, Simulator, VehicleRoute},
+};
+
+/// Re-executes a trace written by
+/// [`TraceRecorder`](crate::callbacks::trace::TraceRecorder).
+pub struct Replayer {
+    checkpoint: Checkpoint,
+    entries: Vec<TraceEntry>,
+}
+
+impl Replayer {
+    pub fn new(checkpoint: Checkpoint, entries: Vec<TraceEntry>) -> Self {
+        Self {
+            checkpoint,
+            entries,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let (checkpoint, entries) = read_trace(path)?;
+        Ok(Self::new(checkpoint, entries))
+    }
+
+    /// Restores the recorded initial state and simulates it with a scheduler
+    /// returning the recorded plans, failing as soon as an event differs from
+    /// the trace. Returns the simulator after the last recorded event.
+    pub fn replay(&self) -> anyhow::Result<Simulator> {
+        let time_interval = self.checkpoint.config().time_interval;
+        let mut plans: VecDeque<(Plan, i32)> = VecDeque::new();
+        let mut expected = VecDeque::new();
+        let mut last_timestep: Option<NaiveDateTime> = None;
+        for entry in self.entries.iter() {
+            match entry {
+                TraceEntry::Event(event) => {
+                    if event.0 == SimulatorEventData::UpdateTimestep {
+                        // the previous dispatch was charged until this timestep
+                        if let (Some((_, intervals)), Some(prev)) =
+                            (plans.back_mut(), last_timestep)
+                        {
+                            *intervals = ((event.1 - prev).num_milliseconds()
+                                / time_interval.num_milliseconds())
+                                as i32;
+                        }
+                        last_timestep = Some(event.1);
+                    }
+                    expected.push_back(event.clone());
+                }
+                TraceEntry::Dispatch(plan) => plans.push_back((plan.clone(), 1)),
+            }
+        }
+
+        let scheduler = RecordedScheduler {
+            plans,
+            time_interval,
+            charged_intervals: 1,
+        };
+        let verifier = VerifyEvents {
+            expected: Rc::new(RefCell::new(expected)),
+            num_seen: 0,
+        };
+        let mut sim = Simulator::from_checkpoint(self.checkpoint.clone(), Box::new(scheduler))?;
+        sim.add_callback(Box::new(verifier.clone()));
+        while !verifier.expected.borrow().is_empty() {
+            if sim.is_finished() {
+                bail!(
+                    "simulation finished with {} recorded events left",
+                    verifier.expected.borrow().len()
+                );
+            }
+            sim.simulate_step()?;
+        }
+        Ok(sim)
+    }
+}
+
+type Plan = MapType<VehicleId, Vec<VehicleRoute>>;
+
+/// Returns the recorded plans in order, charging the number of intervals
+/// the original dispatch took.
+struct RecordedScheduler {
+    plans: VecDeque<(Plan, i32)>,
+    time_interval: Duration,
+    charged_intervals: i32,
+}
+
+impl Scheduler for RecordedScheduler {
+    fn schedule(&mut self, _args: SchedulerArgs) -> Plan {
+        // a missing plan shows up as a diverging event
+        let (plan, intervals) = self.plans.pop_front().unwrap_or_default();
+        self.charged_intervals = intervals.max(1);
+        plan
+    }
+
+    fn charged_time(&self) -> Option<std::time::Duration> {
+        (self.time_interval * (self.charged_intervals - 1))
+            .to_std()
+            .ok()
+    }
+}
+
+#[derive(Clone)]
+struct VerifyEvents {
+    expected: Rc<RefCell<VecDeque<SimEvent>>>,
+    num_seen: usize,
+}
+
+impl SimulationCallback for VerifyEvents {
+    fn visit_event(&mut self, event: &SimEvent) -> anyhow::Result<()> {
+        let expected = self.expected.borrow_mut().pop_front();
+        self.num_seen += 1;
+        match expected {
+            Some(expected) if &expected == event => Ok(()),
+            Some(expected) => Err(anyhow!(
+                "event {} differs from the trace: expected {expected:?}, got {event:?}",
+                self.num_seen
+            )),
+            None => Err(anyhow!(
+                "unexpected event {event:?} after the end of the trace"
+            )),
+        }
+    }
+}
+
+#[test]
+fn test_replay_trace() {
+    use crate::{callbacks::trace::TraceRecorder, testing};
+
+    let path = std::env::temp_dir().join(format!("dpdp_trace_{}.jsonl", std::process::id()));
+    let mut sim = testing::simulator().build().unwrap();
+    sim.add_callback(Box::new(TraceRecorder::new(&path, &sim).unwrap()));
+    sim.simulate_until(sim.start_time() + Duration::hours(8))
+        .unwrap();
+    let expected = serde_json::to_value(sim.result()).unwrap();
+    // flushes the trace
+    drop(sim);
+
+    let replayed = Replayer::load(&path).unwrap().replay().unwrap();
+    assert_eq!(serde_json::to_value(replayed.result()).unwrap(), expected);
+
+    // dropping a dispatch makes the replay diverge
+    let (checkpoint, mut entries) = read_trace(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let index = entries
+        .iter()
+        .position(|e| matches!(e, TraceEntry::Dispatch(plan) if !plan.is_empty()))
+        .unwrap();
+    entries.remove(index);
+    assert!(Replayer::new(checkpoint, entries).replay().is_err());
+}
@@ -0,0 +1,140 @@
+use std::{
+    fs::{create_dir_all, File},
+    path::Path,
+};
+
+use chrono::{Duration, NaiveDateTime};
+use humantime::format_duration;
+use serde::Serialize;
+
+use crate::model::{order::OrderId, vehicle_info::VehicleId, MapType};
+
+use super::objective::Objective;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderResult {
+    pub order_id: OrderId,
+    /// Time the last item of the order was delivered, if it was.
+    pub deliver_time: Option<NaiveDateTime>,
+    pub deadline: NaiveDateTime,
+    /// How late the order was delivered, zero if on time. Orders not
+    /// delivered yet count as late as they are at the time of the result.
+    #[serde(serialize_with = "crate::model::serialize_duration")]
+    pub lateness: Duration,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VehicleResult {
+    pub vehicle_id: VehicleId,
+    pub distance: f32,
+    /// Total time spent away from idle (transporting, docking and loading).
+    #[serde(serialize_with = "crate::model::serialize_duration")]
+    pub busy_time: Duration,
+    /// Fraction of the simulated horizon the vehicle was busy.
+    pub utilization: f64,
+}
+
+/// Outcome of a simulation run.
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationResult {
+    pub orders: Vec<OrderResult>,
+    pub vehicles: Vec<VehicleResult>,
+    /// Time between the start of the simulation and the last delivery.
+    #[serde(serialize_with = "crate::model::serialize_duration")]
+    pub horizon: Duration,
+    #[serde(serialize_with = "crate::model::serialize_duration")]
+    pub total_timeout: Duration,
+    pub total_distance: f32,
+    /// Value of the objective selected in the simulator config.
+    pub objective: f64,
+    /// Values of all objectives in [`Objective::all`], keyed by name.
+    pub objectives: MapType<String, f64>,
+}
+
+impl SimulationResult {
+    pub fn new(
+        orders: Vec<OrderResult>,
+        vehicles: Vec<VehicleResult>,
+        horizon: Duration,
+        objective: Objective,
+    ) -> Self {
+        let total_timeout = orders.iter().map(|o| o.lateness).sum();
+        let total_distance = vehicles.iter().map(|v| v.distance).sum();
+        let mut result = Self {
+            orders,
+            vehicles,
+            horizon,
+            total_timeout,
+            total_distance,
+            objective: 0.0,
+            objectives: MapType::new(),
+        };
+        result.objective = objective.evaluate(&result);
+        result.objectives = Objective::all()
+            .iter()
+            .map(|o| (o.name().to_string(), o.evaluate(&result)))
+            .collect();
+        result
+    }
+
+    pub fn all_delivered(&self) -> bool {
+        self.orders.iter().all(|o| o.deliver_time.is_some())
+    }
+
+    pub fn total_timeout_hours(&self) -> f64 {
+        self.total_timeout.num_seconds() as f64 / 3600.0
+    }
+
+    pub fn average_distance(&self) -> f32 {
+        if self.vehicles.is_empty() {
+            0.0
+        } else {
+            self.total_distance / self.vehicles.len() as f32
+        }
+    }
+
+    pub fn write_json(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let file = create_file(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    pub fn write_orders_csv(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        write_csv(path, &self.orders)
+    }
+
+    pub fn write_vehicles_csv(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        write_csv(path, &self.vehicles)
+    }
+
+    pub fn summary(&self) -> String {
+        let total_timeout = format_duration(self.total_timeout.to_std().unwrap_or_default());
+        let objectives = self
+            .objectives
+            .iter()
+            .map(|(name, value)| format!("{name} {value:.3}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "total timeout {total_timeout} ({}), total distance {}, objective {:.3} ({objectives})",
+            self.total_timeout, self.total_distance, self.objective
+        )
+    }
+}
+
+fn create_file(path: impl AsRef<Path>) -> anyhow::Result<File> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    Ok(File::create(path)?)
+}
+
+fn write_csv<T: Serialize>(path: impl AsRef<Path>, records: &[T]) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(create_file(path)?);
+    for record in records {
+        writer.serialize(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
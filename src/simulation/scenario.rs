@@ -0,0 +1,134 @@
+use chrono::{NaiveTime, Timelike};
+use rand::{seq::IndexedRandom, Rng};
+use rand_distr::{Distribution, Poisson};
+
+use crate::model::{factory_info::FactoryId, order::Order, MapType, ALL_INSTANCES};
+
+const SECONDS_PER_HOUR: u32 = 3600;
+
+/// Samples future orders from the orders of historical instances.
+///
+/// Orders are assumed to arrive as a Poisson process per pickup factory whose
+/// rate is constant within each hour of the day and estimated as the average
+/// number of orders per instance. A sampled order copies the quantities,
+/// delivery factory, load times and time to deadline of a random historical
+/// order of the same factory and hour.
+#[derive(Debug, Clone)]
+pub struct ScenarioSampler {
+    /// Historical orders by pickup factory and hour of creation.
+    history: MapType<(FactoryId, u32), Vec<Order>>,
+    num_instances: usize,
+}
+
+impl ScenarioSampler {
+    pub fn learn(instances: impl IntoIterator<Item = i32>) -> anyhow::Result<Self> {
+        let mut history: MapType<_, Vec<_>> = MapType::new();
+        let mut num_instances = 0;
+        for inst in instances {
+            for order in Order::load_instance(inst)?.into_iter().map(|(_, o)| o) {
+                let key = (order.pickup_id.clone(), order.creation_time.hour());
+                history.entry(key).or_default().push(order);
+            }
+            num_instances += 1;
+        }
+        anyhow::ensure!(num_instances > 0, "no instances to learn from");
+        Ok(Self {
+            history,
+            num_instances,
+        })
+    }
+
+    /// Learns from all benchmark instances except the one being solved.
+    pub fn from_benchmark(exclude: i32) -> anyhow::Result<Self> {
+        Self::learn(ALL_INSTANCES.clone().filter(|&inst| inst != exclude))
+    }
+
+    /// Expected number of orders per unit of `scale` created in `[from, to)`.
+    pub fn expected_orders(&self, from: NaiveTime, to: NaiveTime) -> f64 {
+        self.history
+            .iter()
+            .map(|((_, hour), orders)| self.rate(orders.len()) * overlap(*hour, from, to))
+            .sum()
+    }
+
+    /// Samples the orders created in `[from, to)` with the arrival rates
+    /// multiplied by `scale`. Their ids are `prefix` followed by a counter.
+    pub fn sample<R: Rng>(
+        &self,
+        rng: &mut R,
+        from: NaiveTime,
+        to: NaiveTime,
+        scale: f64,
+        prefix: &str,
+    ) -> Vec<Order> {
+        let mut orders = Vec::new();
+        for ((_, hour), history) in &self.history {
+            let mean = self.rate(history.len()) * overlap(*hour, from, to) * scale;
+            let Ok(poisson) = Poisson::new(mean) else {
+                continue;
+            };
+            let start = from
+                .num_seconds_from_midnight()
+                .max(hour * SECONDS_PER_HOUR);
+            let end = to
+                .num_seconds_from_midnight()
+                .min((hour + 1) * SECONDS_PER_HOUR);
+            for _ in 0..poisson.sample(rng) as usize {
+                let secs = rng.random_range(start..end);
+                let creation_time =
+                    NaiveTime::from_num_seconds_from_midnight_opt(secs, 0).expect("within the day");
+                let template = history.choose(rng).expect("history is not empty");
+                let order_id = format!("{prefix}{}", orders.len());
+                orders.push(template.resampled(order_id, creation_time));
+            }
+        }
+        orders.sort_by_key(|o| o.creation_time);
+        orders
+    }
+
+    /// Orders per hour given how many were observed in all instances.
+    fn rate(&self, count: usize) -> f64 {
+        count as f64 / self.num_instances as f64
+    }
+}
+
+/// Fraction of the hour of the day that lies within `[from, to)`.
+fn overlap(hour: u32, from: NaiveTime, to: NaiveTime) -> f64 {
+    let start = from
+        .num_seconds_from_midnight()
+        .max(hour * SECONDS_PER_HOUR);
+    let end = to
+        .num_seconds_from_midnight()
+        .min((hour + 1) * SECONDS_PER_HOUR);
+    end.saturating_sub(start) as f64 / SECONDS_PER_HOUR as f64
+}
+
+#[test]
+fn test_sampled_orders_are_delivered() {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::simulator::{Simulator, VehicleInitialPosition};
+    use crate::{model::Map, schedule::insertion::InsertionScheduler};
+
+    let sampler = ScenarioSampler::learn(30..=33).unwrap();
+    let from = NaiveTime::from_hms_opt(9, 30, 0).unwrap();
+    let to = NaiveTime::from_hms_opt(11, 0, 0).unwrap();
+    let mut rng = SmallRng::seed_from_u64(727);
+    let orders = sampler.sample(&mut rng, from, to, 0.2, "sampled-");
+    assert!(!orders.is_empty());
+    assert!(orders
+        .iter()
+        .all(|o| from <= o.creation_time && o.creation_time < to));
+    let expected = sampler.expected_orders(from, to) * 0.2;
+    assert!((orders.len() as f64 - expected).abs() < 4.0 * expected.sqrt() + 1.0);
+
+    let mut sim = Simulator::builder(1, VehicleInitialPosition::Random(&mut rng))
+        .scheduler(Box::new(InsertionScheduler))
+        .build()
+        .unwrap();
+    let num_orders = sim.orders().keys().count() + orders.len();
+    sim.inject_orders(orders).unwrap();
+    let result = sim.run_to_completion().unwrap();
+    assert_eq!(result.orders.len(), num_orders);
+    assert!(result.all_delivered());
+}
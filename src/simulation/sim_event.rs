@@ -1,5 +1,5 @@
 use chrono::{Duration, NaiveDateTime};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::model::{
     factory_info::FactoryId,
@@ -9,39 +9,65 @@ use crate::model::{
     Map as _,
 };
 
-use super::event_queue::Event;
+use super::{config::SimulatorConfig, event_queue::Event};
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct VehicleWork {
     pub load_items: Vec<OrderItemId>,
     pub unload_items: Vec<OrderItemId>,
+    // recomputed by the simulator, so external schedulers may omit these
+    #[serde(default)]
     pub load_time: Duration,
+    #[serde(default)]
     pub unload_time: Duration,
 }
 
 impl VehicleWork {
+    /// Creates a work with loading times according to the default
+    /// [`SimulatorConfig`]. The simulator recomputes these with its own
+    /// config once the work is dispatched.
     pub fn new(
         order_items: &OrderItemMap,
         pickup_items: Vec<OrderItemId>,
         delivery_items: Vec<OrderItemId>,
     ) -> Self {
-        let load_time_per_box = Duration::minutes(1);
-        let unload_time_per_box = load_time_per_box;
+        Self::with_config(
+            order_items,
+            pickup_items,
+            delivery_items,
+            &SimulatorConfig::default(),
+        )
+    }
 
-        Self {
-            load_time: load_time_per_box
-                * pickup_items
-                    .iter()
-                    .map(|i| order_items.gets(i).demand)
-                    .sum(),
-            unload_time: unload_time_per_box
-                * delivery_items
-                    .iter()
-                    .map(|i| order_items.gets(i).demand)
-                    .sum(),
+    pub fn with_config(
+        order_items: &OrderItemMap,
+        pickup_items: Vec<OrderItemId>,
+        delivery_items: Vec<OrderItemId>,
+        config: &SimulatorConfig,
+    ) -> Self {
+        let mut work = Self {
             load_items: pickup_items,
             unload_items: delivery_items,
-        }
+            load_time: Duration::zero(),
+            unload_time: Duration::zero(),
+        };
+        work.update_times(order_items, config);
+        work
+    }
+
+    pub fn update_times(&mut self, order_items: &OrderItemMap, config: &SimulatorConfig) {
+        self.load_time = config.load_time_per_box
+            * self
+                .load_items
+                .iter()
+                .map(|i| order_items.gets(i).demand)
+                .sum();
+        self.unload_time = config.unload_time_per_box
+            * self
+                .unload_items
+                .iter()
+                .map(|i| order_items.gets(i).demand)
+                .sum();
     }
 
     pub fn new_load(order_items: &OrderItemMap, pickup_items: Vec<OrderItemId>) -> Self {
@@ -73,7 +99,7 @@ impl VehicleWork {
 }
 
 #[non_exhaustive]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum SimulatorEventData {
     OrderArrival {
         order_id: OrderId,
@@ -93,12 +119,37 @@ pub enum SimulatorEventData {
         vehicle_id: VehicleId,
         factory_id: FactoryId,
         delivered_items: Vec<OrderItemId>,
+        /// Recorded before waiting for the loaded items to be created.
+        delivery_time: NaiveDateTime,
     },
     UpdateTimestep,
 }
 
+impl SimulatorEventData {
+    /// Order of simultaneous events, lowest first:
+    ///
+    /// 0. `OrderArrival`, so new orders are visible to a dispatch at the same time
+    /// 1. `FinishLoading`, freeing the dock before anyone else asks for it
+    /// 2. `VehicleArrival`
+    /// 3. `VehicleApproachedDock`
+    /// 4. `UpdateTimestep`, so the dispatch sees every change at its time
+    pub fn priority(&self) -> u8 {
+        match self {
+            Self::OrderArrival { .. } => 0,
+            Self::FinishLoading { .. } => 1,
+            Self::VehicleArrival { .. } => 2,
+            Self::VehicleApproachedDock { .. } => 3,
+            Self::UpdateTimestep => 4,
+        }
+    }
+}
+
 impl Event for (SimulatorEventData, NaiveDateTime) {
     fn time(&self) -> chrono::NaiveDateTime {
         self.1
     }
+
+    fn priority(&self) -> u8 {
+        self.0.priority()
+    }
 }
@@ -1,21 +1,27 @@
 use anyhow::{anyhow, Context as _};
 use humantime::format_duration;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashSet, VecDeque},
+    collections::{BTreeSet, HashSet, VecDeque},
+    fs::{create_dir_all, File},
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
     time::Instant,
 };
 
-use chrono::{Duration, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
 use rand::{rngs::SmallRng, seq::IndexedRandom, Rng};
 
 use crate::{
     define_map,
     model::{
+        deserialize_map,
         factory_info::{FactoryId, FactoryInfo, FactoryInfoMap},
         order::{Order, OrderId, OrderMap},
         order_item::{OrderItemId, OrderItemMap},
+        read_csv,
         route_info::{RouteInfo, RouteMap},
+        serialize_map,
         vehicle_info::{VehicleId, VehicleInfo, VehicleInfoMap},
         Map, MapType,
     },
@@ -24,11 +30,14 @@ use crate::{
 
 use super::{
     callback::SimulationCallback,
+    config::{InfeasiblePlanPolicy, SimulatorConfig},
+    error::SimulationError,
     event_queue::EventQueue,
+    result::{OrderResult, SimulationResult, VehicleResult},
     sim_event::{SimulatorEventData, VehicleWork},
 };
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VehicleRoute {
     pub destination: FactoryId,
     pub work: VehicleWork,
@@ -53,14 +62,29 @@ impl VehicleRoute {
     }
 }
 
-#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub enum VehiclePosition {
     Idle(FactoryId),
     DoingWork(FactoryId),
     Transporting(FactoryId, FactoryId),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Occupancy of the docks of a factory at a dispatch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FactoryDocks {
+    /// Number of docks (`port_num`).
+    pub num_docks: i32,
+    /// Expected end of the work of every vehicle at a dock, earliest first.
+    pub busy_until: Vec<NaiveDateTime>,
+    /// Vehicles waiting for a dock, first in line first, with the duration
+    /// of their work.
+    pub queue: Vec<(VehicleId, Duration)>,
+    /// Vehicles whose current leg ends here, with the time they reach the
+    /// dock and the duration of their work, earliest first.
+    pub approaching: Vec<(VehicleId, NaiveDateTime, Duration)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OrderItemState {
     // now < creation_time
     Unavailable,
@@ -100,16 +124,20 @@ impl OrderItemState {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VehicleState {
     position: VehiclePosition,
     item_stack: Vec<OrderItemId>,
     allocated_item_stack: Vec<OrderItemId>,
     // planning information
     current_route: VecDeque<VehicleRoute>,
+    // statistics
+    distance: f32,
+    busy_time: Duration,
+    busy_since: Option<NaiveDateTime>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FactoryState {
     num_avail_docks: i32,
     queue: VecDeque<(VehicleId, VehicleWork)>,
@@ -135,6 +163,9 @@ impl VehicleState {
             item_stack: Vec::new(),
             allocated_item_stack: Vec::new(),
             current_route: VecDeque::new(),
+            distance: 0.0,
+            busy_time: Duration::zero(),
+            busy_since: None,
         }
     }
 }
@@ -149,20 +180,20 @@ pub struct Simulator {
     order_items: OrderItemMap,
 
     initial_date: NaiveDate,
-    time_interval: Duration,
+    config: SimulatorConfig,
 
     vehicle_states: VehicleStateMap,
     factory_states: FactoryStateMap,
     order_item_states: OrderItemStateMap,
 
-    dock_approaching_time: Duration,
-
     scheduler: Box<dyn Scheduler>,
 
     events: EventQueue<SimEvent>,
 
     total_distance: f32,
     total_distance_last_timeslot: f32,
+    /// Time of the last handled event.
+    time: NaiveDateTime,
     callbacks: Vec<Box<dyn SimulationCallback>>,
 }
 
@@ -171,53 +202,166 @@ pub enum VehicleInitialPosition<'a, RNG = SmallRng> {
     Random(&'a mut RNG),
 }
 
+#[derive(Deserialize)]
+struct InitialPositionRecord {
+    vehicle_id: VehicleId,
+    factory_id: FactoryId,
+}
+
+impl<RNG> VehicleInitialPosition<'_, RNG> {
+    /// Loads deterministic positions from a CSV file with `vehicle_id` and
+    /// `factory_id` columns.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(Self::Deterministic(
+            read_csv::<InitialPositionRecord>(path)?
+                .into_iter()
+                .map(|r| (r.vehicle_id, r.factory_id))
+                .collect(),
+        ))
+    }
+}
+
 impl<RNG: Rng> VehicleInitialPosition<'_, RNG> {
-    pub fn get(&mut self, vehicle_id: &VehicleId, factories: &[FactoryId]) -> FactoryId {
+    pub fn get(
+        &mut self,
+        vehicle_id: &VehicleId,
+        factories: &[FactoryId],
+    ) -> anyhow::Result<FactoryId> {
         match self {
-            Self::Deterministic(map) => map.get(vehicle_id).unwrap().clone(),
-            Self::Random(rng) => factories.choose(rng).unwrap().clone(),
+            Self::Deterministic(map) => map
+                .get(vehicle_id)
+                .cloned()
+                .ok_or_else(|| anyhow!("no initial position for vehicle {vehicle_id}")),
+            Self::Random(rng) => Ok(factories.choose(rng).unwrap().clone()),
         }
     }
 }
 
-impl Simulator {
-    pub fn new<RNG: Rng>(
-        mut initial_position: VehicleInitialPosition<'_, RNG>,
-        inst_num: i32,
-        callbacks: Vec<Box<dyn SimulationCallback>>,
-    ) -> anyhow::Result<Self> {
-        let orders = Order::load_instance(inst_num).context("unable to load orders")?;
+/// Where the orders and vehicles of a simulation come from.
+///
+/// Factories and routes are shared by every instance and are always loaded
+/// from the standard benchmark files.
+#[derive(Debug, Clone)]
+pub enum InstanceSource {
+    /// One of the numbered instances in `data/benchmark`.
+    Benchmark(i32),
+    /// A directory containing `orders.csv` and `vehicle_info.csv`.
+    Directory(PathBuf),
+}
+
+impl InstanceSource {
+    pub fn load_orders(&self) -> anyhow::Result<OrderMap> {
+        match self {
+            Self::Benchmark(inst_num) => Order::load_instance(*inst_num),
+            Self::Directory(dir) => Order::load(dir.join("orders.csv")),
+        }
+    }
+
+    pub fn load_vehicles(&self) -> anyhow::Result<VehicleInfoMap> {
+        match self {
+            Self::Benchmark(inst_num) => VehicleInfo::load_instance(*inst_num),
+            Self::Directory(dir) => VehicleInfo::load(dir.join("vehicle_info.csv")),
+        }
+    }
+}
+
+impl From<i32> for InstanceSource {
+    fn from(inst_num: i32) -> Self {
+        Self::Benchmark(inst_num)
+    }
+}
+
+pub struct SimulatorBuilder<'a, RNG = SmallRng> {
+    source: InstanceSource,
+    initial_position: VehicleInitialPosition<'a, RNG>,
+    scheduler: Option<Box<dyn Scheduler>>,
+    callbacks: Vec<Box<dyn SimulationCallback>>,
+    config: SimulatorConfig,
+}
+
+impl<'a, RNG: Rng> SimulatorBuilder<'a, RNG> {
+    pub fn new(
+        source: impl Into<InstanceSource>,
+        initial_position: VehicleInitialPosition<'a, RNG>,
+    ) -> Self {
+        Self {
+            source: source.into(),
+            initial_position,
+            scheduler: None,
+            callbacks: Vec::new(),
+            config: SimulatorConfig::default(),
+        }
+    }
+
+    pub fn config(mut self, config: SimulatorConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets the scheduler used for dispatching. Defaults to a
+    /// [`NaiveScheduler`] over the loaded instance.
+    pub fn scheduler(mut self, scheduler: Box<dyn Scheduler>) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    pub fn callback(mut self, callback: Box<dyn SimulationCallback>) -> Self {
+        self.callbacks.push(callback);
+        self
+    }
+
+    pub fn callbacks(mut self, callbacks: Vec<Box<dyn SimulationCallback>>) -> Self {
+        self.callbacks.extend(callbacks);
+        self
+    }
+
+    pub fn build(self) -> anyhow::Result<Simulator> {
+        let Self {
+            source,
+            mut initial_position,
+            scheduler,
+            callbacks,
+            config,
+        } = self;
+
+        let orders = source.load_orders().context("unable to load orders")?;
         let order_items: OrderItemMap = orders
             .values()
             .flat_map(Order::into_items)
             .map(|o| (o.id.clone(), o))
             .collect::<MapType<_, _>>()
             .into();
-        let vehicles = VehicleInfo::load_instance(inst_num).context("unable to load vehicles")?;
+        let vehicles = source.load_vehicles().context("unable to load vehicles")?;
         let factories = FactoryInfo::load_std().context("unable to load factories")?;
         let factory_ids: Vec<_> = factories.keys().cloned().collect();
-        let initial_date = Local::now().date_naive();
+        let initial_date = config.initial_date;
         let vehicle_states = vehicles
             .keys()
             .map(|id| {
-                let init_pos = initial_position.get(id, &factory_ids);
-                (id.clone(), VehicleState::new(init_pos))
+                let init_pos = initial_position.get(id, &factory_ids)?;
+                Ok((id.clone(), VehicleState::new(init_pos)))
             })
-            .collect::<MapType<_, _>>()
+            .collect::<anyhow::Result<MapType<_, _>>>()?
             .into();
         let factory_states = factories
             .iter()
             .map(|(id, info)| (id.clone(), FactoryState::new(info.port_num)))
             .collect::<MapType<_, _>>()
             .into();
+        // clairvoyant schedulers see every order from the start
+        let initial_state = if config.clairvoyant {
+            OrderItemState::Unallocated
+        } else {
+            OrderItemState::Unavailable
+        };
         let order_item_states = order_items
             .keys()
-            .map(|id| (id.clone(), OrderItemState::Unavailable))
+            .map(|id| (id.clone(), initial_state.clone()))
             .collect::<MapType<_, _>>()
             .into();
 
         let mut events = EventQueue::new();
-        for order in orders.values() {
+        for order in orders.values().filter(|_| !config.clairvoyant) {
             events.push((
                 SimulatorEventData::OrderArrival {
                     order_id: order.order_id.clone(),
@@ -227,13 +371,20 @@ impl Simulator {
             ));
         }
 
-        let time_interval = Duration::minutes(100);
         events.push((
             SimulatorEventData::UpdateTimestep,
             initial_date.and_time(NaiveTime::MIN),
         ));
 
-        Ok(Self {
+        let scheduler = match scheduler {
+            Some(scheduler) => scheduler,
+            None => Box::new(NaiveScheduler::from_instance(
+                vehicles.clone(),
+                order_items.clone(),
+            )),
+        };
+
+        Ok(Simulator {
             routes: RouteInfo::load_std()
                 .context("unable to load routes")?
                 .into(),
@@ -243,52 +394,323 @@ impl Simulator {
             order_items,
 
             initial_date,
-            time_interval,
+            config,
 
             vehicle_states,
             factory_states,
             order_item_states,
 
             events,
-            scheduler: Box::new(
-                NaiveScheduler::new(inst_num).context("unable to create scheduler")?,
-            ),
+            scheduler,
 
-            dock_approaching_time: Duration::minutes(30),
             total_distance: 0.0,
             total_distance_last_timeslot: 0.0,
+            time: initial_date.and_time(NaiveTime::MIN),
             callbacks,
         })
     }
+}
+
+impl Simulator {
+    pub fn builder<'a, RNG: Rng>(
+        source: impl Into<InstanceSource>,
+        initial_position: VehicleInitialPosition<'a, RNG>,
+    ) -> SimulatorBuilder<'a, RNG> {
+        SimulatorBuilder::new(source, initial_position)
+    }
+
+    /// Shorthand for a benchmark instance dispatched by the default
+    /// [`NaiveScheduler`].
+    pub fn new<RNG: Rng>(
+        initial_position: VehicleInitialPosition<'_, RNG>,
+        inst_num: i32,
+        callbacks: Vec<Box<dyn SimulationCallback>>,
+    ) -> anyhow::Result<Self> {
+        Self::builder(inst_num, initial_position)
+            .callbacks(callbacks)
+            .build()
+    }
+
+    pub fn config(&self) -> &SimulatorConfig {
+        &self.config
+    }
+
+    pub fn vehicles(&self) -> &VehicleInfoMap {
+        &self.vehicles
+    }
+
+    pub fn factories(&self) -> &FactoryInfoMap {
+        &self.factories
+    }
+
+    pub fn orders(&self) -> &OrderMap {
+        &self.orders
+    }
+
+    pub fn order_items(&self) -> &OrderItemMap {
+        &self.order_items
+    }
+
+    pub fn vehicle_positions(&self) -> MapType<VehicleId, VehiclePosition> {
+        self.vehicle_states
+            .iter()
+            .map(|(id, state)| (id.clone(), state.position.clone()))
+            .collect()
+    }
+
+    pub fn routes(&self) -> &RouteMap {
+        &self.routes
+    }
+
+    /// Arrival time and work of the leg the vehicle is driving, if any.
+    pub fn current_leg(&self, vehicle_id: &VehicleId) -> Option<(NaiveDateTime, &VehicleWork)> {
+        self.events.iter().find_map(|(event, time)| match event {
+            SimulatorEventData::VehicleArrival {
+                vehicle_id: id,
+                work,
+                ..
+            } if id == vehicle_id => Some((*time, work)),
+            _ => None,
+        })
+    }
+
+    /// Estimates when the vehicle finishes the leg it is currently working
+    /// on, ignoring the time spent waiting for a dock. Idle vehicles are
+    /// ready at `now`.
+    pub fn estimate_ready_time(&self, vehicle_id: &VehicleId, now: NaiveDateTime) -> NaiveDateTime {
+        let work_time = |work: &VehicleWork| work.load_time + work.unload_time;
+        for (event, time) in self.events.iter() {
+            match event {
+                SimulatorEventData::VehicleArrival {
+                    vehicle_id: id,
+                    work,
+                    ..
+                } if id == vehicle_id => {
+                    return *time + self.config.dock_approaching_time + work_time(work);
+                }
+                SimulatorEventData::VehicleApproachedDock {
+                    vehicle_id: id,
+                    work,
+                    ..
+                } if id == vehicle_id => return *time + work_time(work),
+                SimulatorEventData::FinishLoading { vehicle_id: id, .. } if id == vehicle_id => {
+                    return *time;
+                }
+                _ => {}
+            }
+        }
+        // waiting in a dock queue
+        self.factory_states
+            .values()
+            .flat_map(|state| state.queue.iter())
+            .find(|(id, _)| id == vehicle_id)
+            .map(|(_, work)| now + work_time(work))
+            .unwrap_or(now)
+    }
+
+    /// Current occupancy of the docks of every factory.
+    pub fn factory_docks(&self) -> MapType<FactoryId, FactoryDocks> {
+        let work_time = |work: &VehicleWork| work.load_time + work.unload_time;
+        let mut docks: MapType<FactoryId, FactoryDocks> = self
+            .factory_states
+            .iter()
+            .map(|(id, state)| {
+                let docks = FactoryDocks {
+                    num_docks: self.factories.gets(id).port_num,
+                    busy_until: Vec::new(),
+                    queue: state
+                        .queue
+                        .iter()
+                        .map(|(vehicle_id, work)| (vehicle_id.clone(), work_time(work)))
+                        .collect(),
+                    approaching: Vec::new(),
+                };
+                (id.clone(), docks)
+            })
+            .collect();
+        for (event, time) in self.events.iter() {
+            match event {
+                SimulatorEventData::FinishLoading { factory_id, .. } => {
+                    if let Some(docks) = docks.get_mut(factory_id) {
+                        docks.busy_until.push(*time);
+                    }
+                }
+                SimulatorEventData::VehicleArrival {
+                    vehicle_id,
+                    factory_id,
+                    work,
+                } => {
+                    if let Some(docks) = docks.get_mut(factory_id) {
+                        docks.approaching.push((
+                            vehicle_id.clone(),
+                            *time + self.config.dock_approaching_time,
+                            work_time(work),
+                        ));
+                    }
+                }
+                SimulatorEventData::VehicleApproachedDock {
+                    vehicle_id,
+                    factory_id,
+                    work,
+                } => {
+                    if let Some(docks) = docks.get_mut(factory_id) {
+                        docks
+                            .approaching
+                            .push((vehicle_id.clone(), *time, work_time(work)));
+                    }
+                }
+                _ => {}
+            }
+        }
+        for docks in docks.values_mut() {
+            docks.busy_until.sort();
+            docks
+                .approaching
+                .sort_by_key(|(id, time, _)| (*time, id.clone()));
+        }
+        docks
+    }
+
+    /// Legs the vehicle will drive after the current one, as planned by the
+    /// last dispatch.
+    pub fn current_route(&self, vehicle_id: &VehicleId) -> Option<&VecDeque<VehicleRoute>> {
+        self.vehicle_states
+            .get(vehicle_id)
+            .map(|state| &state.current_route)
+    }
+
+    pub fn initial_date(&self) -> NaiveDate {
+        self.initial_date
+    }
+
+    /// Midnight of the simulated day, the time of the first dispatch.
+    pub fn start_time(&self) -> NaiveDateTime {
+        self.initial_date.and_time(NaiveTime::MIN)
+    }
 
     fn group_order_item_ids<'a>(ids: impl Iterator<Item = &'a OrderItemId>) -> HashSet<OrderId> {
         ids.map(|id| id.order_id.clone()).collect()
     }
 
-    pub fn simulate_until(&mut self, until: NaiveDateTime) {
+    /// Collects the per-order and per-vehicle outcome of the simulation so
+    /// far. Orders that are not completely delivered yet have no deliver time
+    /// and are as late as they are at the time of the last handled event.
+    pub fn result(&self) -> SimulationResult {
+        let mut deliver_times: MapType<OrderId, Option<NaiveDateTime>> = MapType::new();
+        for (item, state) in self.order_item_states.iter() {
+            let deliver_time = match state {
+                OrderItemState::Delivered { deliver_time, .. } => Some(*deliver_time),
+                _ => None,
+            };
+            deliver_times
+                .entry(item.order_id.clone())
+                .and_modify(|t| *t = t.zip(deliver_time).map(|(a, b)| a.max(b)))
+                .or_insert(deliver_time);
+        }
+
+        let orders: Vec<_> = deliver_times
+            .into_iter()
+            .map(|(order_id, deliver_time)| {
+                let deadline = self
+                    .orders
+                    .gets(&order_id)
+                    .committed_completion_time(self.initial_date);
+                let lateness = (deliver_time.unwrap_or(self.time) - deadline).max(Duration::zero());
+                OrderResult {
+                    order_id,
+                    deliver_time,
+                    deadline,
+                    lateness,
+                }
+            })
+            .collect();
+
+        let start = self.start_time();
+        let horizon = orders
+            .iter()
+            .filter_map(|o| o.deliver_time)
+            .max()
+            .map(|t| t - start)
+            .unwrap_or_else(Duration::zero);
+        let vehicles = self
+            .vehicle_states
+            .iter()
+            .map(|(vehicle_id, state)| VehicleResult {
+                vehicle_id: vehicle_id.clone(),
+                distance: state.distance,
+                busy_time: state.busy_time,
+                utilization: if horizon > Duration::zero() {
+                    state.busy_time.num_seconds() as f64 / horizon.num_seconds() as f64
+                } else {
+                    0.0
+                },
+            })
+            .collect();
+
+        SimulationResult::new(orders, vehicles, horizon, self.config.objective)
+    }
+
+    /// Whether all events are handled, i.e. all orders are delivered.
+    pub fn is_finished(&self) -> bool {
+        self.events.peek().is_none()
+    }
+
+    /// Runs the simulation until no events are left, i.e. all orders are
+    /// delivered. Fails with [`SimulationError::Unfinished`] once
+    /// [`SimulatorConfig::max_duration`] is exceeded.
+    pub fn run_to_completion(&mut self) -> Result<SimulationResult, SimulationError> {
+        while !self.is_finished() {
+            self.simulate_step()?;
+        }
+        Ok(self.result())
+    }
+
+    pub fn simulate_until(&mut self, until: NaiveDateTime) -> Result<(), SimulationError> {
         while self.events.peek().map(|e| e.1 <= until).unwrap_or(false) {
-            self.simulate_step();
+            self.simulate_step()?;
         }
+        Ok(())
     }
 
-    pub fn simulate_step(&mut self) {
+    pub fn simulate_step(&mut self) -> Result<(), SimulationError> {
+        // schedulers that never deliver keep the dispatches going forever
+        let end = self.start_time() + self.config.max_duration;
+        if self.events.peek().is_some_and(|(_, time)| *time > end) {
+            let undelivered = self
+                .result()
+                .orders
+                .iter()
+                .filter(|o| o.deliver_time.is_none())
+                .count();
+            return Err(SimulationError::Unfinished {
+                undelivered,
+                time: self.time,
+            });
+        }
         if let Some((event, time)) = self.events.pop() {
-            self.handle_event(event, time);
+            self.time = time;
+            self.handle_event(event, time)?;
         }
+        Ok(())
     }
 
-    fn handle_event(&mut self, event_data: SimulatorEventData, time: NaiveDateTime) {
-        println!("handling event {event_data:?} at {time}");
+    fn handle_event(
+        &mut self,
+        event_data: SimulatorEventData,
+        time: NaiveDateTime,
+    ) -> Result<(), SimulationError> {
+        tracing::debug!("handling event {event_data:?} at {time}");
         let sim_event = (event_data, time);
-        self.callbacks
-            .iter_mut()
-            .for_each(|cb| cb.visit_event(&sim_event));
+        self.notify_callbacks(time, |cb| cb.visit_event(&sim_event))?;
         let (event_data, time) = sim_event;
         match event_data {
             SimulatorEventData::OrderArrival {
                 order_id,
                 order_item_ids,
-            } => self.handle_order_arrival(order_id, order_item_ids, time),
+            } => {
+                self.handle_order_arrival(order_id, order_item_ids, time);
+                Ok(())
+            }
             SimulatorEventData::VehicleArrival {
                 vehicle_id,
                 factory_id,
@@ -303,10 +725,15 @@ impl Simulator {
                 vehicle_id,
                 factory_id,
                 delivered_items,
-            } => self.handle_finish_load(vehicle_id, factory_id, delivered_items, time),
-            SimulatorEventData::UpdateTimestep => {
-                self.handle_timestep(time);
-            }
+                delivery_time,
+            } => self.handle_finish_load(
+                vehicle_id,
+                factory_id,
+                delivered_items,
+                delivery_time,
+                time,
+            ),
+            SimulatorEventData::UpdateTimestep => self.handle_timestep(time),
         }
     }
 
@@ -316,14 +743,35 @@ impl Simulator {
         factory_id: FactoryId,
         mut work: VehicleWork,
         time: NaiveDateTime,
-    ) {
+    ) -> Result<(), SimulationError> {
+        // items created later are waited for, occupying the dock
+        let start = work
+            .load_items
+            .iter()
+            .map(|i| {
+                self.initial_date
+                    .and_time(self.order_items.gets(i).creation_time)
+            })
+            .fold(time, NaiveDateTime::max);
+        self.expect_position(
+            &vehicle_id,
+            &factory_id,
+            time,
+            |position| matches!(position, VehiclePosition::DoingWork(pos) if pos == &factory_id),
+        )?;
         let state = self.vehicle_states.gets_mut(&vehicle_id);
-        assert!(matches!(&state.position, VehiclePosition::DoingWork(pos) if pos == &factory_id));
         let mut delivered_items = vec![];
         // ensure LIFO constraints
         while let Some(item) = work.unload_items.pop() {
             let corresponding_item = state.item_stack.pop();
-            assert!(corresponding_item.as_ref() == Some(&item));
+            if corresponding_item.as_ref() != Some(&item) {
+                return Err(SimulationError::LifoViolation {
+                    vehicle_id,
+                    item_id: item,
+                    top: corresponding_item,
+                    time,
+                });
+            }
             delivered_items.push(item);
         }
         for item in work.load_items.iter() {
@@ -336,26 +784,43 @@ impl Simulator {
             .map(|i| self.order_items.gets(i).demand)
             .sum();
         // ensure capacity constraints
-        assert!(total_demand <= self.vehicles.gets(&vehicle_id).capacity());
+        let capacity = self.vehicles.gets(&vehicle_id).capacity();
+        if total_demand > capacity {
+            return Err(SimulationError::CapacityViolation {
+                vehicle_id,
+                demand: total_demand,
+                capacity,
+                time,
+            });
+        }
         let total_time = work.load_time + work.unload_time;
+        // only loading waits for the items to be created, not the unloads
+        let unload_time: Duration = delivered_items
+            .iter()
+            .map(|id| self.order_items.gets(id).unload_time)
+            .sum();
+        let delivery_time = time + total_time - self.config.dock_approaching_time - unload_time;
         self.events.push((
             SimulatorEventData::FinishLoading {
                 vehicle_id,
                 factory_id,
                 delivered_items,
+                delivery_time,
             },
-            time + total_time,
+            start + total_time,
         ));
+        Ok(())
     }
 
     fn begin_vehicle_transporting(
         &mut self,
         vehicle_id: VehicleId,
         factory_id: FactoryId,
-        route: VehicleRoute,
+        mut route: VehicleRoute,
         time: NaiveDateTime,
-    ) {
-        println!("vehicle {vehicle_id} is following {route:?} at {time}");
+    ) -> Result<(), SimulationError> {
+        route.work.update_times(&self.order_items, &self.config);
+        tracing::debug!("vehicle {vehicle_id} is following {route:?} at {time}");
         route.work.load_items.iter().for_each(|i| {
             *self.order_item_states.gets_mut(i) = OrderItemState::Allocated;
         });
@@ -363,17 +828,32 @@ impl Simulator {
         let total_time = self
             .routes
             .query_time(factory_id.clone(), route.destination.clone());
+        self.expect_position(
+            &vehicle_id,
+            &factory_id,
+            time,
+            |position| matches!(position, VehiclePosition::Idle(pos) if pos == &factory_id),
+        )?;
         let state = self.vehicle_states.gets_mut(&vehicle_id);
-        assert!(matches!(&state.position, VehiclePosition::Idle(pos) if pos == &factory_id));
-        self.total_distance += self
+        let distance = self
             .routes
             .query_distance(factory_id.clone(), route.destination.clone());
+        self.total_distance += distance;
+        state.distance += distance;
+        state.busy_since = Some(time);
         state.position = VehiclePosition::Transporting(factory_id, route.destination.clone());
 
         // simulate loading and unloading ahead of time
         for unload_item in route.work.unload_items.iter().rev() {
             let item = state.allocated_item_stack.pop();
-            assert!(item.as_ref() == Some(unload_item));
+            if item.as_ref() != Some(unload_item) {
+                return Err(SimulationError::LifoViolation {
+                    vehicle_id,
+                    item_id: unload_item.clone(),
+                    top: item,
+                    time,
+                });
+            }
         }
         state
             .allocated_item_stack
@@ -387,135 +867,315 @@ impl Simulator {
             },
             time + total_time,
         ));
+        Ok(())
     }
 
     fn total_demand(&self, items: &[OrderItemId]) -> i32 {
         items.iter().map(|i| self.order_items.gets(i).demand).sum()
     }
 
-    fn check_order_split(&self, item_ids: &[OrderItemId], capacity: i32) -> anyhow::Result<()> {
-        let orders: HashSet<OrderId> = item_ids.iter().map(|item| item.order_id.clone()).collect();
+    fn order_split_violations(
+        &self,
+        vehicle_id: &VehicleId,
+        item_ids: &[OrderItemId],
+        capacity: i32,
+        time: NaiveDateTime,
+    ) -> Vec<SimulationError> {
+        let orders: BTreeSet<OrderId> = item_ids.iter().map(|item| item.order_id.clone()).collect();
+        let item_set: HashSet<&OrderItemId> = item_ids.iter().collect();
 
+        let mut violations = Vec::new();
         for order_id in orders {
-            let order = self
-                .orders
-                .get(&order_id)
-                .ok_or_else(|| anyhow!("Invalid order ID: {}", order_id))?;
-            if order.calc_demand() <= capacity {
-                let item_set: HashSet<OrderItemId> = item_ids.iter().cloned().collect();
-                if order
+            let Some(order) = self.orders.get(&order_id) else {
+                violations.push(SimulationError::UnknownOrder {
+                    vehicle_id: vehicle_id.clone(),
+                    order_id,
+                    time,
+                });
+                continue;
+            };
+            if order.calc_demand() <= capacity
+                && order
                     .into_items()
                     .iter()
                     .any(|item| !item_set.contains(&item.id))
-                {
-                    return Err(anyhow!(
-                                "Order {} has demand {} < capacity {} is split (orders can only be split if the demand exceeds vehicle capacity)",
-                                order_id, order.calc_demand(), capacity
-                            ));
-                }
+            {
+                violations.push(SimulationError::IllegalSplit {
+                    vehicle_id: vehicle_id.clone(),
+                    order_id,
+                    demand: order.calc_demand(),
+                    capacity,
+                    time,
+                });
             }
         }
+        violations
+    }
 
+    fn notify_callbacks(
+        &mut self,
+        time: NaiveDateTime,
+        mut visit: impl FnMut(&mut dyn SimulationCallback) -> anyhow::Result<()>,
+    ) -> Result<(), SimulationError> {
+        for callback in self.callbacks.iter_mut() {
+            visit(callback.as_mut()).map_err(|err| SimulationError::Callback {
+                message: format!("{err:#}"),
+                time,
+            })?;
+        }
         Ok(())
     }
 
+    /// Validates the scheduler output and applies the configured
+    /// [`InfeasiblePlanPolicy`], returning the plans that should be followed.
     fn check_planned_routes(
-        &self,
-        planned_routes: &MapType<VehicleId, Vec<VehicleRoute>>,
-    ) -> anyhow::Result<()> {
-        for (vehicle_id, routes) in planned_routes {
-            let info = self
-                .vehicles
-                .get(vehicle_id)
-                .ok_or_else(|| anyhow!("Invalid vehicle ID: {}", vehicle_id))?;
-            let state = self
-                .vehicle_states
-                .get(vehicle_id)
-                .ok_or_else(|| anyhow!("Invalid vehicle ID: {}", vehicle_id))?;
-
-            let mut total_demand = self.total_demand(&state.allocated_item_stack);
-            let mut item_stack = state.allocated_item_stack.clone();
-            assert!(total_demand <= info.capacity());
-            let mut item_states = self.order_item_states.clone();
-            for route in routes {
-                total_demand += route.delta_demand(&self.order_items);
-                if total_demand > info.capacity() {
-                    return Err(anyhow!(
-                        "Violate capacity constraint on vehicle {}!",
-                        vehicle_id
-                    ));
+        &mut self,
+        mut planned_routes: MapType<VehicleId, Vec<VehicleRoute>>,
+        time: NaiveDateTime,
+    ) -> Result<MapType<VehicleId, Vec<VehicleRoute>>, SimulationError> {
+        let policy = self.config.infeasible_plan_policy;
+        let mut rejected = Vec::new();
+        for (vehicle_id, routes) in planned_routes.iter() {
+            if let Err(err) = self.check_planned_vehicle_routes(vehicle_id, routes, time) {
+                if policy == InfeasiblePlanPolicy::Abort {
+                    return Err(err);
                 }
+                rejected.push(err);
+            }
+        }
 
-                for item in route.work.unload_items.iter().rev() {
-                    if item_stack.pop().as_ref() != Some(item) {
-                        return Err(anyhow!(
-                            "Violate LIFO constraint on vehicle {}!",
-                            vehicle_id
-                        ));
+        for err in rejected.iter() {
+            tracing::warn!("rejected plan: {err}");
+            let Some(routes) = err.vehicle_id().map(|id| &planned_routes[id]) else {
+                continue;
+            };
+            self.notify_callbacks(time, |cb| cb.visit_rejected_plan(routes, err))?;
+        }
+
+        match policy {
+            InfeasiblePlanPolicy::KeepPrevious if !rejected.is_empty() => Ok(MapType::new()),
+            InfeasiblePlanPolicy::DropVehicle => {
+                let mut dropped: Vec<VehicleId> = rejected
+                    .iter()
+                    .filter_map(SimulationError::vehicle_id)
+                    .cloned()
+                    .collect();
+                for vehicle_id in dropped.iter() {
+                    planned_routes.remove(vehicle_id);
+                }
+                // the previous routes of dropped vehicles still load items
+                // that are unallocated, so other plans must leave them alone
+                while let Some(owner) = dropped.pop() {
+                    let conflicts = self.item_conflicts(&owner, &planned_routes, time);
+                    for err in conflicts {
+                        let vehicle_id = err.vehicle_id().unwrap().clone();
+                        let Some(routes) = planned_routes.remove(&vehicle_id) else {
+                            continue;
+                        };
+                        tracing::warn!("rejected plan: {err}");
+                        self.notify_callbacks(time, |cb| cb.visit_rejected_plan(&routes, &err))?;
+                        dropped.push(vehicle_id);
                     }
+                }
+                Ok(planned_routes)
+            }
+            _ => Ok(planned_routes),
+        }
+    }
 
-                    let item_info = self
-                        .order_items
-                        .get(item)
-                        .ok_or_else(|| anyhow!("Invalid order item ID: {}", item))?;
-                    if item_info.delivery_id != route.destination {
-                        return Err(anyhow!(
-                            "Order item {} delivery location is {}, not {}!",
-                            item,
-                            item_info.delivery_id,
-                            route.destination
-                        ));
+    /// Plans that load an item the current route of `owner` loads later.
+    fn item_conflicts(
+        &self,
+        owner: &VehicleId,
+        planned_routes: &MapType<VehicleId, Vec<VehicleRoute>>,
+        time: NaiveDateTime,
+    ) -> Vec<SimulationError> {
+        let Some(state) = self.vehicle_states.get(owner) else {
+            return Vec::new();
+        };
+        let claimed: HashSet<&OrderItemId> = state
+            .current_route
+            .iter()
+            .flat_map(|route| route.work.load_items.iter())
+            .collect();
+        planned_routes
+            .iter()
+            .filter_map(|(vehicle_id, routes)| {
+                routes
+                    .iter()
+                    .flat_map(|route| route.work.load_items.iter())
+                    .find(|item| claimed.contains(item))
+                    .map(|item| SimulationError::ItemConflict {
+                        vehicle_id: vehicle_id.clone(),
+                        item_id: item.clone(),
+                        owner: owner.clone(),
+                        time,
+                    })
+            })
+            .collect()
+    }
+
+    fn check_planned_vehicle_routes(
+        &self,
+        vehicle_id: &VehicleId,
+        routes: &[VehicleRoute],
+        time: NaiveDateTime,
+    ) -> Result<(), SimulationError> {
+        match self
+            .plan_violations(vehicle_id, routes, time)
+            .into_iter()
+            .next()
+        {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Every constraint the plan of a vehicle violates, in the order the
+    /// vehicle would run into them. Checking continues after a violation as
+    /// if the offending operation had succeeded.
+    pub fn plan_violations(
+        &self,
+        vehicle_id: &VehicleId,
+        routes: &[VehicleRoute],
+        time: NaiveDateTime,
+    ) -> Vec<SimulationError> {
+        let mut violations = Vec::new();
+        let (Some(info), Some(state)) = (
+            self.vehicles.get(vehicle_id),
+            self.vehicle_states.get(vehicle_id),
+        ) else {
+            violations.push(SimulationError::UnknownVehicle {
+                vehicle_id: vehicle_id.clone(),
+                time,
+            });
+            return violations;
+        };
+
+        let mut total_demand = self.total_demand(&state.allocated_item_stack);
+        let mut item_stack = state.allocated_item_stack.clone();
+        // only possible in a snapshot that is already inconsistent
+        if total_demand > info.capacity() {
+            violations.push(SimulationError::CapacityViolation {
+                vehicle_id: vehicle_id.clone(),
+                demand: total_demand,
+                capacity: info.capacity(),
+                time,
+            });
+        }
+        let mut item_states = self.order_item_states.clone();
+        for route in routes {
+            let unknown_items: Vec<_> = route
+                .work
+                .load_items
+                .iter()
+                .chain(route.work.unload_items.iter())
+                .filter(|item| !self.order_items.contains_key(*item))
+                .collect();
+            if !unknown_items.is_empty() {
+                violations.extend(unknown_items.into_iter().map(|item| {
+                    SimulationError::UnknownItem {
+                        vehicle_id: vehicle_id.clone(),
+                        item_id: item.clone(),
+                        time,
                     }
+                }));
+                // nothing else about this route can be checked reliably
+                continue;
+            }
 
-                    let item_state = item_states
-                        .get_mut(item)
-                        .ok_or_else(|| anyhow!("Invalid order item ID: {}", item))?;
-                    if *item_state != OrderItemState::Allocated
-                        && *item_state != OrderItemState::PickedUp
-                    {
-                        return Err(anyhow!(
-                            "Order item {} is scheduled to be unloaded, but it is not allocated or picked up yet!",
-                            item
-                        ));
+            total_demand += route.delta_demand(&self.order_items);
+            if total_demand > info.capacity() {
+                violations.push(SimulationError::CapacityViolation {
+                    vehicle_id: vehicle_id.clone(),
+                    demand: total_demand,
+                    capacity: info.capacity(),
+                    time,
+                });
+            }
+
+            for item in route.work.unload_items.iter().rev() {
+                let top = item_stack.last().cloned();
+                if top.as_ref() == Some(item) {
+                    item_stack.pop();
+                } else {
+                    violations.push(SimulationError::LifoViolation {
+                        vehicle_id: vehicle_id.clone(),
+                        item_id: item.clone(),
+                        top,
+                        time,
+                    });
+                    if let Some(index) = item_stack.iter().rposition(|i| i == item) {
+                        item_stack.remove(index);
                     }
+                }
 
-                    *item_state = OrderItemState::delivered_irrelevant();
+                let item_info = self.order_items.gets(item);
+                if item_info.delivery_id != route.destination {
+                    violations.push(SimulationError::WrongDeliveryFactory {
+                        vehicle_id: vehicle_id.clone(),
+                        item_id: item.clone(),
+                        expected: item_info.delivery_id.clone(),
+                        actual: route.destination.clone(),
+                        time,
+                    });
                 }
 
-                item_stack.reserve(route.work.load_items.len());
-                for item in &route.work.load_items {
-                    let item_info = self.order_items.gets(item);
-                    if item_info.pickup_id != route.destination {
-                        return Err(anyhow!(
-                            "Order item {} pickup location is {}, not {}!",
-                            item,
-                            item_info.pickup_id,
-                            route.destination
-                        ));
-                    }
-                    item_stack.push(item.clone());
-
-                    let item_state = item_states
-                        .get_mut(item)
-                        .ok_or_else(|| anyhow!("Invalid order item ID: {}", item))?;
-                    if *item_state != OrderItemState::Unallocated {
-                        return Err(anyhow!(
-                            "Order item {} is scheduled to be loaded, but it is not unallocated yet!",
-                            item
-                        ));
-                    }
-                    *item_state = OrderItemState::PickedUp;
+                let item_state = item_states.gets_mut(item);
+                if *item_state != OrderItemState::Allocated
+                    && *item_state != OrderItemState::PickedUp
+                {
+                    violations.push(SimulationError::InvalidItemState {
+                        vehicle_id: vehicle_id.clone(),
+                        item_id: item.clone(),
+                        state: item_state.clone(),
+                        time,
+                    });
                 }
 
-                self.check_order_split(&route.work.load_items, info.capacity())?;
-                self.check_order_split(&route.work.unload_items, info.capacity())?;
+                *item_state = OrderItemState::delivered_irrelevant();
+            }
+
+            item_stack.reserve(route.work.load_items.len());
+            for item in &route.work.load_items {
+                let item_info = self.order_items.gets(item);
+                if item_info.pickup_id != route.destination {
+                    violations.push(SimulationError::WrongPickupFactory {
+                        vehicle_id: vehicle_id.clone(),
+                        item_id: item.clone(),
+                        expected: item_info.pickup_id.clone(),
+                        actual: route.destination.clone(),
+                        time,
+                    });
+                }
+                item_stack.push(item.clone());
+
+                let item_state = item_states.gets_mut(item);
+                if *item_state != OrderItemState::Unallocated {
+                    violations.push(SimulationError::InvalidItemState {
+                        vehicle_id: vehicle_id.clone(),
+                        item_id: item.clone(),
+                        state: item_state.clone(),
+                        time,
+                    });
+                }
+                *item_state = OrderItemState::PickedUp;
+            }
+
+            for items in [&route.work.load_items, &route.work.unload_items] {
+                violations.extend(self.order_split_violations(
+                    vehicle_id,
+                    items,
+                    info.capacity(),
+                    time,
+                ));
             }
         }
 
-        Ok(())
+        violations
     }
 
-    fn handle_timestep(&mut self, time: NaiveDateTime) {
+    fn handle_timestep(&mut self, time: NaiveDateTime) -> Result<(), SimulationError> {
         let distance_travelled = self.total_distance - self.total_distance_last_timeslot;
 
         self.total_distance_last_timeslot = self.total_distance;
@@ -530,15 +1190,11 @@ impl Simulator {
             .iter()
             .map(|(id, state)| (id.clone(), state.allocated_item_stack.clone()))
             .collect::<MapType<_, _>>();
-        let vehicle_positions = self
-            .vehicle_states
-            .iter()
-            .map(|(id, state)| (id.clone(), state.position.clone()))
-            .collect::<MapType<_, _>>();
+        let vehicle_positions = self.vehicle_positions();
 
         let start = Instant::now();
-        let sim = self.fork(Box::new(NoopScheduler), Some(time));
-        // let args = SchedulerArgs::new(sim);
+        let static_deadline = (!self.config.clairvoyant).then_some(time);
+        let sim = self.fork(Box::new(NoopScheduler), static_deadline);
         let args = SchedulerArgs {
             items: order_items.into(),
             item_states: self.order_item_states.clone(),
@@ -546,30 +1202,79 @@ impl Simulator {
             vehicle_positions,
             time,
             elapsed_distance: distance_travelled,
+            factory_docks: self.factory_docks(),
             static_simulator: sim,
         };
-        self.callbacks
-            .iter_mut()
-            .for_each(|cb| cb.visit_dispatch_input(&args));
-        let planned_routes = self.scheduler.schedule(args);
-        self.callbacks
-            .iter_mut()
-            .for_each(|cb| cb.visit_dispatch_output(&planned_routes));
-        println!("planned route: {:?}", planned_routes);
-
-        let schedule_time = start.elapsed();
-        let intervals =
-            1 + (schedule_time.as_nanos() / self.time_interval.to_std().unwrap().as_nanos()) as i32;
-        println!(
-            "scheduling time: {} ({} intervals)",
-            format_duration(schedule_time),
+        self.notify_callbacks(time, |cb| cb.visit_dispatch_input(&args))?;
+        let planned_routes =
+            self.scheduler
+                .try_schedule(args)
+                .map_err(|err| SimulationError::Scheduler {
+                    message: format!("{err:#}"),
+                    time,
+                })?;
+        self.notify_callbacks(time, |cb| cb.visit_dispatch_output(&planned_routes))?;
+        tracing::debug!("planned route: {:?}", planned_routes);
+
+        let intervals = if self.config.charge_scheduling_time {
+            let schedule_time = self
+                .scheduler
+                .charged_time()
+                .unwrap_or_else(|| start.elapsed());
+            let intervals = 1
+                + (schedule_time.as_nanos()
+                    / self.config.time_interval.to_std().unwrap().as_nanos())
+                    as i32;
+            tracing::debug!(
+                "scheduling time: {} ({} intervals)",
+                format_duration(schedule_time),
+                intervals
+            );
             intervals
-        );
+        } else {
+            1
+        };
+
+        self.apply_plan(planned_routes, time)?;
 
-        if let Err(err) = self.check_planned_routes(&planned_routes) {
-            panic!("invalid planning routes: {}", err);
+        if let Some((item, _)) = self
+            .order_item_states
+            .iter()
+            .find(|(_, s)| !matches!(s, OrderItemState::Delivered { .. }))
+        {
+            tracing::debug!("{item} is not delivered yet, continuing simulation");
+            self.events.push((
+                SimulatorEventData::UpdateTimestep,
+                time + self.config.time_interval * intervals,
+            ));
+        } else {
+            let result = self.result();
+            for order in result.orders.iter() {
+                if let Some(deliver_time) = order.deliver_time {
+                    tracing::debug!(
+                        "{} lateness: {} ({} - {})",
+                        order.order_id,
+                        order.lateness,
+                        deliver_time,
+                        order.deadline
+                    );
+                }
+            }
+            tracing::debug!("all items are delivered, {}", result.summary());
         }
 
+        Ok(())
+    }
+
+    /// Validates a plan like the output of a dispatch at `time` and makes the
+    /// vehicles follow it. Idle vehicles leave immediately.
+    pub fn apply_plan(
+        &mut self,
+        plan: MapType<VehicleId, Vec<VehicleRoute>>,
+        time: NaiveDateTime,
+    ) -> Result<(), SimulationError> {
+        let planned_routes = self.check_planned_routes(plan, time)?;
+
         for (vehicle_id, routes) in planned_routes {
             let state = self.vehicle_states.gets_mut(&vehicle_id);
             state.current_route.clear();
@@ -577,64 +1282,72 @@ impl Simulator {
 
             if let VehiclePosition::Idle(start) = state.position.clone() {
                 if let Some(dest) = state.current_route.pop_front() {
-                    self.begin_vehicle_transporting(vehicle_id, start.clone(), dest, time);
+                    self.begin_vehicle_transporting(vehicle_id, start.clone(), dest, time)?;
                 }
             }
         }
+        Ok(())
+    }
 
-        if let Some((item, _)) = self
-            .order_item_states
-            .iter()
-            .find(|(_, s)| !matches!(s, OrderItemState::Delivered { .. }))
-        {
-            println!("{item} is not delivered yet, continuing simulation");
+    /// Schedules a dispatch at `time`.
+    pub fn request_dispatch(&mut self, time: NaiveDateTime) {
+        self.events.push((SimulatorEventData::UpdateTimestep, time));
+    }
+
+    /// Adds orders that arrive at their creation time, e.g. sampled future
+    /// orders in a fork. Their creation time must not be in the past.
+    pub fn inject_orders(&mut self, orders: impl IntoIterator<Item = Order>) -> anyhow::Result<()> {
+        for order in orders {
+            anyhow::ensure!(
+                !self.orders.contains_key(&order.order_id),
+                "order {} already exists",
+                order.order_id
+            );
+            for factory_id in [&order.pickup_id, &order.delivery_id] {
+                anyhow::ensure!(
+                    self.factories.contains_key(factory_id),
+                    "unknown factory {factory_id:?} of order {}",
+                    order.order_id
+                );
+            }
+
+            let items = order.into_items();
             self.events.push((
-                SimulatorEventData::UpdateTimestep,
-                time + self.time_interval * intervals,
+                SimulatorEventData::OrderArrival {
+                    order_id: order.order_id.clone(),
+                    order_item_ids: items.iter().map(|item| item.id.clone()).collect(),
+                },
+                self.initial_date.and_time(order.creation_time),
             ));
-        } else {
-            let mut order_timeouts: MapType<OrderId, Duration> = Default::default();
-            let mut order_deliver_times: MapType<OrderId, NaiveDateTime> = Default::default();
-            for (item, state) in self.order_item_states.iter() {
-                let timeout = order_timeouts
-                    .entry(item.order_id.clone())
-                    .or_insert(Duration::MIN);
-                let max_timeout = (*timeout).max(state.timeout());
-                *timeout = max_timeout;
-                let order_deliver_time = order_deliver_times
-                    .entry(item.order_id.clone())
-                    .or_insert(NaiveDateTime::MIN);
-                if let OrderItemState::Delivered { deliver_time, .. } = state {
-                    let max_deliver_time = (*order_deliver_time).max(*deliver_time);
-                    *order_deliver_time = max_deliver_time;
-                }
+            for item in items {
+                self.order_item_states
+                    .insert(item.id.clone(), OrderItemState::Unavailable);
+                self.order_items.insert(item.id.clone(), item);
             }
-            let total_timeout: Duration = order_timeouts
-                .values()
-                .map(|t| (*t).max(Duration::zero()))
-                .sum();
-            let total_timeout_str = format_duration(total_timeout.to_std().unwrap());
-            let total_distance = self.total_distance;
-            for (order_id, timeout) in order_timeouts {
-                let deliver_time = order_deliver_times
-                    .get(&order_id)
-                    .unwrap()
-                    .and_local_timezone(Local)
-                    .unwrap()
-                    .timestamp();
-                let deadline = self
-                    .orders
-                    .gets(&order_id)
-                    .committed_completion_time(self.initial_date)
-                    .and_local_timezone(Local)
-                    .unwrap()
-                    .timestamp();
-                println!("{order_id} timeout: {timeout} ({deliver_time} - {deadline})");
-            }
-            println!(
-                "all items are delivered, total timeout {total_timeout_str} ({total_timeout}), total distance {total_distance}"
-            );
+            self.orders.insert(order.order_id.clone(), order);
+        }
+        Ok(())
+    }
+
+    /// Events only happen to vehicles in the matching position, unless the
+    /// state was restored from an inconsistent checkpoint.
+    fn expect_position(
+        &self,
+        vehicle_id: &VehicleId,
+        factory_id: &FactoryId,
+        time: NaiveDateTime,
+        expected: impl FnOnce(&VehiclePosition) -> bool,
+    ) -> Result<(), SimulationError> {
+        let position = &self.vehicle_states.gets(vehicle_id).position;
+        if expected(position) {
+            return Ok(());
         }
+        Err(SimulationError::UnexpectedPosition {
+            vehicle_id: vehicle_id.clone(),
+            factory_id: factory_id.clone(),
+            position: position.clone(),
+            time,
+        })
     }
 
     fn handle_order_arrival(
@@ -654,11 +1367,11 @@ impl Simulator {
         factory_id: FactoryId,
         work: VehicleWork,
         time: NaiveDateTime,
-    ) {
+    ) -> Result<(), SimulationError> {
+        self.expect_position(&vehicle_id, &factory_id, time, |position| {
+            matches!(position, VehiclePosition::Transporting(_, dest) if dest == &factory_id)
+        })?;
         let state = self.vehicle_states.gets_mut(&vehicle_id);
-        assert!(
-            matches!(&state.position, VehiclePosition::Transporting(_, dest) if dest == &factory_id)
-        );
         state.position = VehiclePosition::DoingWork(factory_id.clone());
 
         self.events.push((
@@ -667,8 +1380,9 @@ impl Simulator {
                 factory_id,
                 work,
             },
-            time + self.dock_approaching_time,
+            time + self.config.dock_approaching_time,
         ));
+        Ok(())
     }
 
     fn handle_vehicle_approached_dock(
@@ -677,15 +1391,16 @@ impl Simulator {
         factory_id: FactoryId,
         work: VehicleWork,
         time: NaiveDateTime,
-    ) {
+    ) -> Result<(), SimulationError> {
         let state = self.factory_states.gets_mut(&factory_id);
         if state.num_avail_docks == 0 {
-            println!("factory {factory_id} is full, waiting...");
+            tracing::debug!("factory {factory_id} is full, waiting...");
             state.queue.push_back((vehicle_id, work));
         } else {
             state.num_avail_docks -= 1;
-            self.begin_vehicle_loading(vehicle_id, factory_id, work, time);
+            self.begin_vehicle_loading(vehicle_id, factory_id, work, time)?;
         }
+        Ok(())
     }
 
     fn handle_finish_load(
@@ -693,36 +1408,41 @@ impl Simulator {
         vehicle_id: VehicleId,
         factory_id: FactoryId,
         delivered_items: Vec<OrderItemId>,
+        delivery_time: NaiveDateTime,
         time: NaiveDateTime,
-    ) {
+    ) -> Result<(), SimulationError> {
         let factory = self.factory_states.gets_mut(&factory_id);
         if let Some((vehicle_id, work)) = factory.queue.pop_front() {
-            self.begin_vehicle_loading(vehicle_id, factory_id.clone(), work, time);
+            self.begin_vehicle_loading(vehicle_id, factory_id.clone(), work, time)?;
         } else {
             factory.num_avail_docks += 1;
         }
 
-        println!("{delivered_items:?} are delivered");
-        let unload_time: Duration = delivered_items
-            .iter()
-            .map(|id| self.order_items.gets(id).unload_time)
-            .sum();
-
+        tracing::debug!("{delivered_items:?} are delivered");
         for item in delivered_items.iter() {
             let item_info = self.order_items.gets(item);
             *self.order_item_states.gets_mut(item) = OrderItemState::delivered(
                 item_info.committed_completion_time(self.initial_date),
-                time - self.dock_approaching_time - unload_time,
+                delivery_time,
             );
         }
 
+        self.expect_position(
+            &vehicle_id,
+            &factory_id,
+            time,
+            |position| matches!(position, VehiclePosition::DoingWork(pos) if pos == &factory_id),
+        )?;
         let state = self.vehicle_states.gets_mut(&vehicle_id);
-        assert!(matches!(&state.position, VehiclePosition::DoingWork(pos) if pos == &factory_id));
         state.position = VehiclePosition::Idle(factory_id.clone());
+        if let Some(since) = state.busy_since.take() {
+            state.busy_time += time - since;
+        }
 
         if let Some(dest) = state.current_route.pop_front() {
-            self.begin_vehicle_transporting(vehicle_id, factory_id, dest, time);
+            self.begin_vehicle_transporting(vehicle_id, factory_id, dest, time)?;
         }
+        Ok(())
     }
 
     pub fn fork(
@@ -732,6 +1452,8 @@ impl Simulator {
     ) -> Self {
         let mut orders = self.orders.clone();
         let mut order_items = self.order_items.clone();
+        let mut order_item_states = self.order_item_states.clone();
+        let mut events = self.events.clone();
 
         if let Some(static_deadline) = static_deadline {
             orders.retain(|_, order| {
@@ -740,6 +1462,12 @@ impl Simulator {
             order_items.retain(|_, item| {
                 self.initial_date.and_time(item.creation_time) <= static_deadline
             });
+            // later orders never arrive in the fork
+            order_item_states.retain(|id, _| order_items.contains_key(id));
+            events.retain(|(event, _)| match event {
+                SimulatorEventData::OrderArrival { order_id, .. } => orders.contains_key(order_id),
+                _ => true,
+            });
         }
 
         Self {
@@ -748,17 +1476,665 @@ impl Simulator {
             vehicles: self.vehicles.clone(),
             orders,
             order_items,
-            initial_date: self.initial_date.clone(),
-            time_interval: self.time_interval.clone(),
+            initial_date: self.initial_date,
+            config: self.config.clone(),
             vehicle_states: self.vehicle_states.clone(),
             factory_states: self.factory_states.clone(),
-            order_item_states: self.order_item_states.clone(),
-            dock_approaching_time: self.dock_approaching_time.clone(),
+            order_item_states,
             scheduler,
-            events: self.events.clone(),
-            total_distance: self.total_distance.clone(),
-            total_distance_last_timeslot: self.total_distance_last_timeslot.clone(),
+            events,
+            total_distance: self.total_distance,
+            total_distance_last_timeslot: self.total_distance_last_timeslot,
+            time: self.time,
             callbacks: self.callbacks.clone(),
         }
     }
+
+    /// Forks for simulating ahead privately: without callbacks, and without
+    /// charging scheduling time so that the outcome is reproducible.
+    pub fn rollout_fork(&self, scheduler: Box<dyn Scheduler>) -> Self {
+        let mut sim = self.fork(scheduler, None);
+        sim.callbacks.clear();
+        sim.config.charge_scheduling_time = false;
+        sim
+    }
+
+    /// Extracts the static part of the current state, restricted to the
+    /// factories referenced by known orders and vehicles.
+    pub fn static_instance(&self) -> StaticInstance {
+        let mut factory_ids: HashSet<&FactoryId> = HashSet::new();
+        for order in self.orders.values() {
+            factory_ids.extend([&order.pickup_id, &order.delivery_id]);
+        }
+        for state in self.vehicle_states.values() {
+            match &state.position {
+                VehiclePosition::Idle(id) | VehiclePosition::DoingWork(id) => {
+                    factory_ids.insert(id);
+                }
+                VehiclePosition::Transporting(from, to) => factory_ids.extend([from, to]),
+            }
+            factory_ids.extend(state.current_route.iter().map(|r| &r.destination));
+        }
+        StaticInstance {
+            initial_date: self.initial_date,
+            config: self.config.clone(),
+            vehicles: self.vehicles.clone(),
+            factories: self
+                .factories
+                .iter()
+                .filter(|(id, _)| factory_ids.contains(id))
+                .map(|(id, info)| (id.clone(), info.clone()))
+                .collect::<MapType<_, _>>()
+                .into(),
+            routes: self.routes.subset(&factory_ids),
+            orders: self.orders.clone(),
+        }
+    }
+
+    /// Extracts what a scheduler needs besides the dynamic state in
+    /// [`SchedulerArgs`]: the static instance and the state of the current
+    /// vehicle legs.
+    pub fn dispatch_instance(&self) -> DispatchInstance {
+        DispatchInstance {
+            instance: self.static_instance(),
+            events: self.events.clone(),
+            dock_queues: self
+                .factory_states
+                .iter()
+                .filter(|(_, state)| !state.queue.is_empty())
+                .map(|(id, state)| (id.clone(), state.queue.iter().cloned().collect()))
+                .collect(),
+        }
+    }
+
+    /// Rebuilds a simulator at `time` from a [`DispatchInstance`] and the
+    /// dynamic state a scheduler sees. Vehicles finish their current legs
+    /// like in the original.
+    pub fn from_dispatch_instance(
+        instance: DispatchInstance,
+        item_states: OrderItemStateMap,
+        vehicle_stacks: &MapType<VehicleId, Vec<OrderItemId>>,
+        vehicle_positions: &MapType<VehicleId, VehiclePosition>,
+        time: NaiveDateTime,
+    ) -> anyhow::Result<Self> {
+        let DispatchInstance {
+            instance:
+                StaticInstance {
+                    initial_date,
+                    config,
+                    vehicles,
+                    factories,
+                    routes,
+                    orders,
+                },
+            events,
+            dock_queues,
+        } = instance;
+
+        let order_items: OrderItemMap = orders
+            .values()
+            .flat_map(Order::into_items)
+            .map(|o| (o.id.clone(), o))
+            .collect::<MapType<_, _>>()
+            .into();
+        let mut item_states = item_states;
+        item_states.retain(|id, _| order_items.contains_key(id));
+        let vehicle_states = vehicles
+            .keys()
+            .map(|id| {
+                let position = vehicle_positions
+                    .get(id)
+                    .ok_or_else(|| anyhow!("no position for vehicle {id}"))?;
+                let mut state = VehicleState::new(FactoryId(String::new()));
+                state.position = position.clone();
+                state.allocated_item_stack = vehicle_stacks.get(id).cloned().unwrap_or_default();
+                state.item_stack = state
+                    .allocated_item_stack
+                    .iter()
+                    .filter(|item| item_states.get(*item) == Some(&OrderItemState::PickedUp))
+                    .cloned()
+                    .collect();
+                Ok((id.clone(), state))
+            })
+            .collect::<anyhow::Result<MapType<_, _>>>()?
+            .into();
+        let mut factory_states: FactoryStateMap = factories
+            .iter()
+            .map(|(id, info)| (id.clone(), FactoryState::new(info.port_num)))
+            .collect::<MapType<_, _>>()
+            .into();
+        for (event, _) in events.iter() {
+            if let SimulatorEventData::FinishLoading { factory_id, .. } = event {
+                factory_states
+                    .get_mut(factory_id)
+                    .ok_or_else(|| anyhow!("unknown factory {factory_id}"))?
+                    .num_avail_docks -= 1;
+            }
+        }
+        for (factory_id, queue) in dock_queues {
+            factory_states
+                .get_mut(&factory_id)
+                .ok_or_else(|| anyhow!("unknown factory {factory_id}"))?
+                .queue = queue.into();
+        }
+
+        Ok(Self {
+            routes,
+            factories,
+            vehicles,
+            orders,
+            order_items,
+            initial_date,
+            config,
+            vehicle_states,
+            factory_states,
+            order_item_states: item_states,
+            scheduler: Box::new(NoopScheduler),
+            events,
+            total_distance: 0.0,
+            total_distance_last_timeslot: 0.0,
+            time,
+            callbacks: Vec::new(),
+        })
+    }
+
+    pub fn add_callback(&mut self, callback: Box<dyn SimulationCallback>) {
+        self.callbacks.push(callback);
+    }
+
+    /// Captures the complete simulation state, including pending events.
+    /// The scheduler and callbacks are not part of it.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            version: CHECKPOINT_VERSION,
+            instance: StaticInstance {
+                initial_date: self.initial_date,
+                config: self.config.clone(),
+                vehicles: self.vehicles.clone(),
+                factories: self.factories.clone(),
+                routes: self.routes.clone(),
+                orders: self.orders.clone(),
+            },
+            vehicle_states: self.vehicle_states.clone(),
+            factory_states: self.factory_states.clone(),
+            order_item_states: self.order_item_states.clone(),
+            events: self.events.clone(),
+            total_distance: self.total_distance,
+            total_distance_last_timeslot: self.total_distance_last_timeslot,
+            time: self.time,
+        }
+    }
+
+    /// Restores a simulation from a [`Checkpoint`], which continues exactly
+    /// like the original would have given the same scheduler decisions.
+    pub fn from_checkpoint(
+        checkpoint: Checkpoint,
+        scheduler: Box<dyn Scheduler>,
+    ) -> anyhow::Result<Self> {
+        if checkpoint.version != CHECKPOINT_VERSION {
+            return Err(anyhow!(
+                "unsupported checkpoint version {}, expected {CHECKPOINT_VERSION}",
+                checkpoint.version
+            ));
+        }
+
+        let StaticInstance {
+            initial_date,
+            config,
+            vehicles,
+            factories,
+            routes,
+            orders,
+        } = checkpoint.instance;
+        let order_items: OrderItemMap = orders
+            .values()
+            .flat_map(Order::into_items)
+            .map(|o| (o.id.clone(), o))
+            .collect::<MapType<_, _>>()
+            .into();
+        Ok(Self {
+            routes,
+            factories,
+            vehicles,
+            orders,
+            order_items,
+            initial_date,
+            config,
+            vehicle_states: checkpoint.vehicle_states,
+            factory_states: checkpoint.factory_states,
+            order_item_states: checkpoint.order_item_states,
+            scheduler,
+            events: checkpoint.events,
+            total_distance: checkpoint.total_distance,
+            total_distance_last_timeslot: checkpoint.total_distance_last_timeslot,
+            time: checkpoint.time,
+            callbacks: Vec::new(),
+        })
+    }
+
+    /// Writes [`Simulator::checkpoint`] as JSON.
+    pub fn save_checkpoint(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        let file =
+            File::create(path).with_context(|| format!("unable to create {}", path.display()))?;
+        serde_json::to_writer(BufWriter::new(file), &self.checkpoint())?;
+        Ok(())
+    }
+
+    /// Restores a simulation saved by [`Simulator::save_checkpoint`].
+    pub fn load_checkpoint(
+        path: impl AsRef<Path>,
+        scheduler: Box<dyn Scheduler>,
+    ) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("unable to open {}", path.display()))?;
+        let checkpoint = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("invalid checkpoint {}", path.display()))?;
+        Self::from_checkpoint(checkpoint, scheduler)
+    }
+}
+
+const CHECKPOINT_VERSION: u32 = 1;
+
+impl Checkpoint {
+    pub fn config(&self) -> &SimulatorConfig {
+        &self.instance.config
+    }
+}
+
+/// Complete state of a [`Simulator`], see [`Simulator::checkpoint`].
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    version: u32,
+    instance: StaticInstance,
+    #[serde(serialize_with = "serialize_map", deserialize_with = "deserialize_map")]
+    vehicle_states: VehicleStateMap,
+    #[serde(serialize_with = "serialize_map", deserialize_with = "deserialize_map")]
+    factory_states: FactoryStateMap,
+    #[serde(serialize_with = "serialize_map", deserialize_with = "deserialize_map")]
+    order_item_states: OrderItemStateMap,
+    events: EventQueue<SimEvent>,
+    total_distance: f32,
+    total_distance_last_timeslot: f32,
+    time: NaiveDateTime,
+}
+
+/// The static part of a simulation: the problem instance and the simulator
+/// configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StaticInstance {
+    pub initial_date: NaiveDate,
+    pub config: SimulatorConfig,
+    #[serde(serialize_with = "serialize_map", deserialize_with = "deserialize_map")]
+    pub vehicles: VehicleInfoMap,
+    #[serde(serialize_with = "serialize_map", deserialize_with = "deserialize_map")]
+    pub factories: FactoryInfoMap,
+    pub routes: RouteMap,
+    /// Orders created so far, or all orders in a checkpoint.
+    #[serde(serialize_with = "serialize_map", deserialize_with = "deserialize_map")]
+    pub orders: OrderMap,
+}
+
+/// The parts of a simulation a scheduler needs besides the dynamic state in
+/// [`SchedulerArgs`]. Serialized in place of [`SchedulerArgs::static_simulator`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DispatchInstance {
+    #[serde(flatten)]
+    pub instance: StaticInstance,
+    /// Pending events, including the ends of the legs the vehicles are
+    /// driving or working on.
+    pub events: EventQueue<SimEvent>,
+    /// Vehicles waiting for a dock, first in line first.
+    pub dock_queues: MapType<FactoryId, Vec<(VehicleId, VehicleWork)>>,
+}
+
+#[test]
+fn test_builder_with_custom_scheduler() {
+    use crate::testing;
+
+    let mut sim = testing::simulator()
+        .scheduler(Box::new(NoopScheduler))
+        .build()
+        .unwrap();
+    sim.simulate_step().unwrap();
+    assert!(sim
+        .vehicle_states
+        .values()
+        .all(|s| s.current_route.is_empty()));
+}
+
+#[test]
+fn test_deterministic_runs() {
+    use crate::testing;
+
+    let run = || {
+        let mut sim = testing::simulator().build().unwrap();
+        serde_json::to_string(&sim.run_to_completion().unwrap()).unwrap()
+    };
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn test_undelivered_orders_stop_the_run() {
+    use crate::testing;
+
+    let mut sim = testing::simulator()
+        .config(SimulatorConfig {
+            max_duration: Duration::days(2),
+            ..Default::default()
+        })
+        .scheduler(Box::new(NoopScheduler))
+        .build()
+        .unwrap();
+    let err = sim.run_to_completion().unwrap_err();
+    assert!(matches!(
+        err,
+        SimulationError::Unfinished {
+            undelivered: 50,
+            ..
+        }
+    ));
+    // undelivered orders are late by the time the run stops
+    let result = sim.result();
+    assert!(!result.all_delivered());
+    assert!(result.orders.iter().all(|o| o.lateness > Duration::zero()));
+}
+
+#[test]
+fn test_inconsistent_state_is_a_violation() {
+    use crate::testing;
+
+    let mut sim = testing::simulator().build().unwrap();
+    let time = sim.start_time();
+    let vehicle_id = sim.vehicles.keys().next().unwrap().clone();
+    let state = sim.vehicle_states.gets_mut(&vehicle_id);
+    state.allocated_item_stack = sim.order_items.keys().cloned().collect();
+    let violations = sim.plan_violations(&vehicle_id, &[], time);
+    assert!(matches!(
+        violations[..],
+        [SimulationError::CapacityViolation { .. }]
+    ));
+
+    // an event for a vehicle that is somewhere else
+    let factory_id = sim.factories.keys().next().unwrap().clone();
+    let work = VehicleWork::new(&sim.order_items, vec![], vec![]);
+    let err = sim
+        .handle_vehicle_arrival(vehicle_id, factory_id, work, time)
+        .unwrap_err();
+    assert!(matches!(err, SimulationError::UnexpectedPosition { .. }));
+}
+
+/// Unloads an item that has never been picked up.
+#[cfg(test)]
+struct UnloadFirstItem;
+
+#[cfg(test)]
+impl Scheduler for UnloadFirstItem {
+    fn schedule(&mut self, args: SchedulerArgs) -> MapType<VehicleId, Vec<VehicleRoute>> {
+        let mut plan = MapType::new();
+        if let Some((item_id, item)) = args.items.iter().next() {
+            let vehicle_id = args.vehicle_stacks.keys().next().unwrap().clone();
+            let work = VehicleWork::new_unload(&args.items, vec![item_id.clone()]);
+            plan.insert(
+                vehicle_id,
+                vec![VehicleRoute::new(item.delivery_id.clone(), work)],
+            );
+        }
+        plan
+    }
+}
+
+#[test]
+fn test_invalid_plan_is_an_error() {
+    use crate::testing;
+
+    let mut sim = testing::simulator()
+        .scheduler(Box::new(UnloadFirstItem))
+        .build()
+        .unwrap();
+    let err = sim.run_to_completion().unwrap_err();
+    assert!(matches!(
+        err,
+        SimulationError::LifoViolation { top: None, .. }
+    ));
+}
+
+#[test]
+fn test_keep_previous_plan_policy() {
+    use crate::testing::{self, Recorder};
+
+    let recorder = Recorder::default();
+    let mut sim = testing::simulator()
+        .config(SimulatorConfig {
+            infeasible_plan_policy: InfeasiblePlanPolicy::KeepPrevious,
+            ..Default::default()
+        })
+        .scheduler(Box::new(UnloadFirstItem))
+        .callback(Box::new(recorder.clone()))
+        .build()
+        .unwrap();
+    sim.simulate_until(sim.start_time() + Duration::hours(6))
+        .unwrap();
+    // dispatches at 01:40, 03:20 and 05:00 are rejected
+    assert_eq!(recorder.rejected().len(), 3);
+    assert!(sim
+        .vehicle_states
+        .values()
+        .all(|s| s.current_route.is_empty()));
+}
+
+#[test]
+fn test_drop_vehicle_plan_policy() {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{schedule::insertion::InsertionScheduler, testing};
+
+    /// Plans properly until the target is set, then only unloads an item
+    /// never picked up with the target, while the others wait.
+    #[derive(Clone, Default)]
+    struct BreakVehicle(Rc<RefCell<Option<VehicleId>>>);
+
+    impl Scheduler for BreakVehicle {
+        fn schedule(&mut self, args: SchedulerArgs) -> MapType<VehicleId, Vec<VehicleRoute>> {
+            let Some(target) = self.0.borrow().clone() else {
+                return InsertionScheduler.schedule(args);
+            };
+            let (item_id, item) = args
+                .items
+                .iter()
+                .find(|(id, _)| {
+                    matches!(
+                        args.item_states.gets(id),
+                        OrderItemState::Unavailable | OrderItemState::Unallocated
+                    )
+                })
+                .unwrap();
+            let work = VehicleWork::new_unload(&args.items, vec![item_id.clone()]);
+            let mut plan: MapType<_, _> = args
+                .vehicle_stacks
+                .keys()
+                .map(|id| (id.clone(), Vec::new()))
+                .collect();
+            plan.insert(
+                target,
+                vec![VehicleRoute::new(item.delivery_id.clone(), work)],
+            );
+            plan
+        }
+    }
+
+    let scheduler = BreakVehicle::default();
+    let mut sim = testing::simulator()
+        .config(SimulatorConfig {
+            infeasible_plan_policy: InfeasiblePlanPolicy::DropVehicle,
+            ..Default::default()
+        })
+        .scheduler(Box::new(scheduler.clone()))
+        .build()
+        .unwrap();
+    let dispatch = sim.start_time() + Duration::minutes(200);
+    sim.simulate_until(dispatch - Duration::seconds(1)).unwrap();
+    let routes = |sim: &Simulator| -> MapType<VehicleId, Vec<(FactoryId, VehicleWork)>> {
+        sim.vehicle_states
+            .iter()
+            .map(|(id, s)| {
+                let routes = s.current_route.iter();
+                let routes = routes.map(|r| (r.destination.clone(), r.work.clone()));
+                (id.clone(), routes.collect())
+            })
+            .collect()
+    };
+    let before = routes(&sim);
+    let (target, _) = before.iter().find(|(_, r)| !r.is_empty()).unwrap();
+    *scheduler.0.borrow_mut() = Some(target.clone());
+
+    // the plan of the target is dropped, the others are applied
+    sim.simulate_until(dispatch).unwrap();
+    let after = routes(&sim);
+    assert_eq!(after[target], before[target]);
+    assert!(after
+        .iter()
+        .all(|(id, routes)| id == target || routes.is_empty()));
+}
+
+#[test]
+fn test_drop_vehicle_keeps_items_of_previous_route() {
+    use crate::{
+        schedule::insertion::InsertionScheduler,
+        testing::{self, Recorder},
+    };
+
+    let recorder = Recorder::default();
+    let mut sim = testing::simulator()
+        .config(SimulatorConfig {
+            infeasible_plan_policy: InfeasiblePlanPolicy::DropVehicle,
+            ..Default::default()
+        })
+        .scheduler(Box::new(InsertionScheduler))
+        .callback(Box::new(recorder.clone()))
+        .build()
+        .unwrap();
+    // a vehicle with a later leg that loads, and an empty rival
+    let (target, rival, later_leg) = loop {
+        sim.simulate_step().unwrap();
+        let target = sim.vehicle_states.iter().find_map(|(id, state)| {
+            let leg = state
+                .current_route
+                .iter()
+                .find(|r| !r.work.load_items.is_empty())?;
+            Some((id.clone(), leg.clone()))
+        });
+        let rival = sim.vehicle_states.iter().find(|(_, state)| {
+            state.allocated_item_stack.is_empty() && state.current_route.is_empty()
+        });
+        if let (Some((target, leg)), Some((rival, _))) = (target, rival) {
+            break (target, rival.clone(), leg);
+        }
+    };
+    let items = later_leg.work.load_items.clone();
+    let unallocated = |sim: &Simulator| {
+        items
+            .iter()
+            .all(|item| *sim.order_item_states.gets(item) == OrderItemState::Unallocated)
+    };
+    assert!(unallocated(&sim));
+    let before = sim.vehicle_states.gets(&target).current_route.len();
+
+    // the target unloads an item it never loaded, the rival takes its items
+    let item = sim.order_items.gets(&items[0]);
+    let broken = VehicleRoute::new(
+        item.delivery_id.clone(),
+        VehicleWork::new_unload(&sim.order_items, vec![item.id.clone()]),
+    );
+    let mut plan = MapType::new();
+    plan.insert(target.clone(), vec![broken]);
+    plan.insert(rival.clone(), vec![later_leg]);
+    sim.apply_plan(plan, sim.time).unwrap();
+
+    let rejected = recorder.rejected();
+    assert_eq!(rejected.len(), 2);
+    assert_eq!(rejected[0].vehicle_id(), Some(&target));
+    assert!(matches!(
+        &rejected[1],
+        SimulationError::ItemConflict { vehicle_id, owner, .. }
+            if vehicle_id == &rival && owner == &target
+    ));
+    assert_eq!(sim.vehicle_states.gets(&target).current_route.len(), before);
+    assert!(sim.vehicle_states.gets(&rival).current_route.is_empty());
+    assert!(matches!(
+        sim.vehicle_states.gets(&rival).position,
+        VehiclePosition::Idle(_)
+    ));
+    assert!(unallocated(&sim));
+}
+
+#[test]
+fn test_checkpoint_resume() {
+    use crate::testing;
+
+    let config = SimulatorConfig::default();
+    let mut sim = testing::simulator().config(config).build().unwrap();
+    sim.simulate_until(sim.start_time() + Duration::hours(6))
+        .unwrap();
+
+    let path = std::env::temp_dir().join(format!("dpdp_checkpoint_{}.json", std::process::id()));
+    sim.save_checkpoint(&path).unwrap();
+    let mut restored =
+        Simulator::load_checkpoint(&path, Box::new(NaiveScheduler::new(1).unwrap())).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let expected = sim.run_to_completion().unwrap();
+    let result = restored.run_to_completion().unwrap();
+    assert_eq!(
+        serde_json::to_value(&result).unwrap(),
+        serde_json::to_value(&expected).unwrap()
+    );
+}
+
+#[test]
+fn test_order_arrival_visible_to_simultaneous_dispatch() {
+    use rand::SeedableRng;
+    use std::fs;
+
+    use crate::testing::Recorder;
+
+    // a single order created exactly at the second dispatch (01:40)
+    let dir = std::env::temp_dir().join(format!("dpdp_tie_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("orders.csv"),
+        "order_id,q_standard,q_small,q_box,demand,creation_time,committed_completion_time,load_time,unload_time,pickup_id,delivery_id\n\
+         0100000001,1,0,0,1.0,01:40:00,05:40:00,240,240,2445d4bd004c457d95957d6ecf77f759,b6dd694ae05541dba369a2a759d2c2b9\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("vehicle_info.csv"),
+        "car_num,capacity,operation_time,gps_id\nV_1,15,24,G_1\n",
+    )
+    .unwrap();
+
+    let recorder = Recorder::default();
+    let mut rng = SmallRng::seed_from_u64(727);
+    let mut sim = Simulator::builder(
+        InstanceSource::Directory(dir.clone()),
+        VehicleInitialPosition::Random(&mut rng),
+    )
+    .scheduler(Box::new(NoopScheduler))
+    .callback(Box::new(recorder.clone()))
+    .build()
+    .unwrap();
+    sim.simulate_until(sim.start_time() + Duration::hours(2))
+        .unwrap();
+    fs::remove_dir_all(dir).unwrap();
+
+    let dispatches = recorder.dispatches();
+    assert_eq!(dispatches.len(), 2);
+    let args = &dispatches[1].args;
+    assert_eq!(args.time, sim.start_time() + Duration::minutes(100));
+    let states: Vec<_> = args.item_states.values().cloned().collect();
+    assert_eq!(states, [OrderItemState::Unallocated]);
 }
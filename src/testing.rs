@@ -0,0 +1,119 @@
+//! Fixtures shared by the tests.
+
+use std::{
+    cell::{Ref, RefCell},
+    rc::Rc,
+    sync::OnceLock,
+};
+
+use rand::{rngs::SmallRng, SeedableRng};
+
+use crate::{
+    model::{factory_info::FactoryId, vehicle_info::VehicleId, MapType},
+    schedule::SchedulerArgs,
+    simulation::{
+        callback::SimulationCallback,
+        error::SimulationError,
+        simulator::{
+            Simulator, SimulatorBuilder, VehicleInitialPosition, VehiclePosition, VehicleRoute,
+        },
+    },
+};
+
+type Plan = MapType<VehicleId, Vec<VehicleRoute>>;
+
+/// Initial positions of the vehicles of instance 1, drawn once with a fixed
+/// seed.
+pub fn initial_positions() -> MapType<VehicleId, FactoryId> {
+    static POSITIONS: OnceLock<MapType<VehicleId, FactoryId>> = OnceLock::new();
+    POSITIONS
+        .get_or_init(|| {
+            let mut rng = SmallRng::seed_from_u64(727);
+            let sim = Simulator::builder(1, VehicleInitialPosition::Random(&mut rng))
+                .build()
+                .unwrap();
+            sim.vehicle_positions()
+                .into_iter()
+                .map(|(vehicle_id, position)| match position {
+                    VehiclePosition::Idle(factory_id) => (vehicle_id, factory_id),
+                    position => panic!("{vehicle_id} starts at {position:?}"),
+                })
+                .collect()
+        })
+        .clone()
+}
+
+/// Simulator of instance 1 with the same initial positions in every test.
+pub fn simulator() -> SimulatorBuilder<'static> {
+    Simulator::builder(
+        1,
+        VehicleInitialPosition::Deterministic(initial_positions()),
+    )
+}
+
+/// A dispatch seen by a [`Recorder`].
+#[derive(Clone)]
+pub struct Dispatch {
+    /// The input as the scheduler received it.
+    pub args: SchedulerArgs,
+    /// Serialized input, as a scheduler process would receive it.
+    pub input: String,
+    /// Empty until the scheduler returns.
+    pub output: Plan,
+}
+
+impl Dispatch {
+    /// Deserializes the input, like a scheduler process would.
+    pub fn parse(&self) -> SchedulerArgs {
+        serde_json::from_str(&self.input).unwrap()
+    }
+}
+
+/// Records every dispatch and rejected plan of a simulation.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    dispatches: Rc<RefCell<Vec<Dispatch>>>,
+    rejected: Rc<RefCell<Vec<SimulationError>>>,
+}
+
+impl Recorder {
+    pub fn dispatches(&self) -> Ref<'_, Vec<Dispatch>> {
+        self.dispatches.borrow()
+    }
+
+    /// Deserialized inputs of every dispatch.
+    pub fn inputs(&self) -> Vec<SchedulerArgs> {
+        self.dispatches().iter().map(Dispatch::parse).collect()
+    }
+
+    pub fn rejected(&self) -> Ref<'_, Vec<SimulationError>> {
+        self.rejected.borrow()
+    }
+}
+
+impl SimulationCallback for Recorder {
+    fn visit_dispatch_input(&mut self, input: &SchedulerArgs) -> anyhow::Result<()> {
+        self.dispatches.borrow_mut().push(Dispatch {
+            args: input.clone(),
+            input: serde_json::to_string(input)?,
+            output: MapType::new(),
+        });
+        Ok(())
+    }
+
+    fn visit_dispatch_output(&mut self, output: &Plan) -> anyhow::Result<()> {
+        if let Some(dispatch) = self.dispatches.borrow_mut().last_mut() {
+            dispatch.output = output.clone();
+        }
+        Ok(())
+    }
+
+    fn visit_rejected_plan(
+        &mut self,
+        _routes: &[VehicleRoute],
+        error: &SimulationError,
+    ) -> anyhow::Result<()> {
+        self.rejected.borrow_mut().push(error.clone());
+        Ok(())
+    }
+}